@@ -46,7 +46,7 @@ fn m_setup(app: &mut app::App) -> FrameData {
         //glh::Vao::new_from_attrib(gl, &attribs, glow::TRIANGLES, &shader).unwrap()
     };
 
-    let (cube_vao, cube_shader) = Cuboid::new_with_uniform_size(0.5).get_vao_and_shader(gl); //glh::Vao::new_from_attrib(gl, &geo_attribs, glow::TRIANGLES, &shader).unwrap();
+    let (cube_vao, cube_shader) = Cuboid::new_with_uniform_size(0.5).normals().get_vao_and_shader(gl); //glh::Vao::new_from_attrib(gl, &geo_attribs, glow::TRIANGLES, &shader).unwrap();
 
     let aspect_ratio = app.input_state.window_size.0 as f32 / app.input_state.window_size.1 as f32;
     let camera = OrbitCamera::new(aspect_ratio, 45.0, 0.01, 1000.0);
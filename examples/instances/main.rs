@@ -2,6 +2,7 @@ extern crate piralib;
 use glow::*;
 use piralib::app;
 use piralib::egui;
+use piralib::event;
 use piralib::gl_helper as glh;
 use rand::Rng;
 
@@ -11,7 +12,6 @@ struct FrameData {
     vao: glh::Vao,
     shader: glh::GlslProg,
     time: f32,
-    number_of_instances: i32,
 
     base_color: [f32; 3],
     tip_color: [f32; 3],
@@ -20,14 +20,21 @@ struct FrameData {
 fn m_setup(app: &mut app::App) -> FrameData {
     let gl = &app.gl;
 
-    #[cfg(not(target_arch = "wasm32"))]
-    let shader_version = "#version 400";
-
-    #[cfg(target_arch = "wasm32")]
-    let shader_version = "#version 300 es";
+    // shared snippet, pulled in by the vertex shader with #include
+    let rotation_glsl = "
+    #pragma once
+    mat3 rotationZ(float angle)
+    {
+        return mat3(
+            vec3( cos(angle), -sin(angle), 0.0),
+            vec3( sin(angle), cos(angle),  0.0),
+            vec3( 0.0,        0.0,         1.0 ));
+    }
+    ";
 
-    let vertex_shader_string = format!("{}
-    precision highp float;
+    // the preprocessor adds the #version ( and precision on web ) for the active backend
+    let vertex_shader_string = "
+    #include \"rotation.glsl\"
 
     uniform mat4 uModelMatrix;
     uniform mat4 uPerspectiveMatrix;
@@ -38,37 +45,32 @@ fn m_setup(app: &mut app::App) -> FrameData {
 
     in vec3 inPosition;
     in vec4 inColor; 
-    in vec2 instancePosition;
+    in mat4 instanceMatrix; // per instance translation, rotation and scale
 
     out vec4 vColor;
     void main()
-    {{   
+    {   
 
+        vec2 instancePosition = instanceMatrix[3].xy;
         float dist = (distance(uMousePos, instancePosition.xy) / 50.0);
 
         float angle =  float(gl_InstanceID) * 0.0001 + instancePosition.y * 0.007;
         angle = (angle + dist) + uTime * -0.01 ;
-        mat3  rotation = mat3(
-            vec3( cos(angle), -sin(angle), 0.0),
-            vec3( sin(angle), cos(angle),  0.0),
-            vec3( 0.0,        0.0,         1.0 ));
+        mat3  rotation = rotationZ(angle);
         
 
         float fur_length = (sin(angle) + 1.0) / 2.0;
         vec3 pos = inPosition;
         pos.x = (pos.x - 5.0 )* ( 1.0 -  inColor.r + 0.0 ) ;
         pos.z = pos.z + 1.0 * inColor.r;
-        vec3 rotatedPoint =  rotation * vec3(pos * (fur_length + 0.5)) + vec3(instancePosition, 0.0);
+        vec3 rotatedPoint =  rotation * vec3(pos * (fur_length + 0.5));
 
-        gl_Position = uPerspectiveMatrix * uViewMatrix * uModelMatrix * vec4(rotatedPoint, 1.0);
+        gl_Position = uPerspectiveMatrix * uViewMatrix * uModelMatrix * instanceMatrix * vec4(rotatedPoint, 1.0);
         vColor = inColor;
-    }}   
-    ", shader_version);
-
-    let frag_shader_string = format!(
-        "{}
-    precision highp float;
+    }   
+    ";
 
+    let frag_shader_string = "
     in vec4 vColor;
 
     uniform vec3 uTipColor;
@@ -76,13 +78,11 @@ fn m_setup(app: &mut app::App) -> FrameData {
 
     out vec4 Color;
     void main()
-    {{   
+    {   
         float alpha = vColor.g;
         Color = vec4(mix(uBaseColor, uTipColor, vColor.g), 1.0);
-    }}
-    ",
-        shader_version
-    );
+    }
+    ";
 
     // build vertex data ----
     let fur_width = 10.0;
@@ -105,8 +105,36 @@ fn m_setup(app: &mut app::App) -> FrameData {
     colors.append(&mut vec![1.0, 0.9, 0.1, 1.0]);
     colors.append(&mut vec![0.0, 0.1, 0.1, 1.0]);
 
-    //create the instance position attribute buffer
-    let mut instance_positions: Vec<f32> = Vec::new();
+    //create the instance matrix attribute buffer
+    let instance_matrices = gen_instance_matrices();
+    println!("number of instances: {}", instance_matrices.len());
+
+    let instance_matrices_attrib = glh::VertexAttrib::new_mat4("instanceMatrix", &instance_matrices, true);
+
+    let shader = glh::ShaderPreprocessor::new()
+        .add_file("rotation.glsl", rotation_glsl)
+        .build(gl, vertex_shader_string, frag_shader_string)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let attribs = vec![
+        glh::VertexAttrib::new_position_attr_with_data(&vertices),
+        glh::VertexAttrib::new_color_attr_with_data(&colors),
+        instance_matrices_attrib,
+    ];
+
+    let vao = glh::Vao::new_from_attrib(gl, &attribs, glow::TRIANGLES, &shader).unwrap();
+
+    FrameData {
+        vao,
+        shader,
+        time: 0.0,
+
+        base_color: [0.2, 0.1, 0.1],
+        tip_color: [0.9, 0.0, 0.2],
+    }
+}
+
+fn gen_instance_matrices() -> Vec<glam::Mat4> {
+    let mut instance_matrices: Vec<glam::Mat4> = Vec::new();
     let spacing = 10.;
     let mut rng = rand::thread_rng();
 
@@ -128,37 +156,24 @@ fn m_setup(app: &mut app::App) -> FrameData {
         for k in 0..max_y {
             let x = ((max_x as f32) - (i as f32)) + rng.gen_range(-random_range..random_range);
             let y = ((max_y as f32) - (k as f32)) + rng.gen_range(-random_range..random_range);
-            instance_positions.append(&mut vec![x as f32 * spacing * 0.5, y as f32 * spacing]);
+            let position = glam::vec3(x as f32 * spacing * 0.5, y as f32 * spacing, 0.0);
+            let rotation = glam::Quat::from_rotation_z(rng.gen_range(-0.3..0.3));
+            let scale = glam::Vec3::splat(rng.gen_range(0.6..1.2));
+
+            instance_matrices.push(glam::Mat4::from_scale_rotation_translation(scale, rotation, position));
         }
     }
 
-    let number_of_instances = instance_positions.len() as i32 / 2;
-    println!("number of instances: {}", number_of_instances);
-
-    let instance_positions_attrib =
-        glh::VertexAttrib::new("instancePosition", 2, 0, &instance_positions, true);
-
-    let shader = glh::GlslProg::new(
-        gl,
-        vertex_shader_string.as_str(),
-        frag_shader_string.as_str(),
-    );
-    let attribs = vec![
-        glh::VertexAttrib::new_position_attr_with_data(&vertices),
-        glh::VertexAttrib::new_color_attr_with_data(&colors),
-        instance_positions_attrib,
-    ];
-
-    let vao = glh::Vao::new_from_attrib(gl, &attribs, glow::TRIANGLES, &shader).unwrap();
-
-    FrameData {
-        vao,
-        shader,
-        number_of_instances,
-        time: 0.0,
+    instance_matrices
+}
 
-        base_color: [0.2, 0.1, 0.1],
-        tip_color: [0.9, 0.0, 0.2],
+fn m_event(app: &mut app::App, data: &mut FrameData, event: &event::WindowEvent) {
+    // shuffle the instances in place, without rebuilding the vao
+    if let event::WindowEvent::KeyboardInput { input, .. } = event {
+        if matches!(input.state, event::ElementState::Released) {
+            data.vao
+                .update_attrib(&app.gl, "instanceMatrix", &gen_instance_matrices());
+        }
     }
 }
 
@@ -228,7 +243,7 @@ fn m_update(app: &app::App, _data: &mut FrameData, ui: &egui::Context) {
         &model_view,
     );
 
-    vao.draw_instanced(gl, _data.number_of_instances);
+    vao.draw_instanced(gl, vao.get_num_of_instances() as i32);
 
     shader.unbind(gl);
 
@@ -245,5 +260,6 @@ fn main() {
         },
         m_setup,
     )
+    .event(m_event)
     .run(m_update);
 }
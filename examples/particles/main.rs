@@ -52,11 +52,21 @@ fn m_setup(app: &mut app::App) -> FrameData {
 
     //texture_attrib.data = texure_vertices;
 
-    let shader = glh::StockShader::new().color().build(&app.gl);
+    // every particle is one instance, with its own transform and color
+    let shader = glh::StockShader::new()
+        .color()
+        .instanced_transform()
+        .instanced_color()
+        .build(&app.gl);
+
+    let transforms: Vec<glam::Mat4> = Vec::new();
+    let instance_colors: Vec<glam::Vec4> = Vec::new();
 
     let attribs = vec![
         glh::VertexAttrib::new_position_attr_with_data(&vertices),
         glh::VertexAttrib::new_color_attr_with_data(&colors),
+        glh::VertexAttrib::new_instance_transform_attr_with_data(&transforms),
+        glh::VertexAttrib::new_instance_color_attr_with_data(&instance_colors),
     ];
     let vao = glh::Vao::new_from_attrib(&app.gl, &attribs, glow::TRIANGLES, &shader).unwrap();
 
@@ -73,7 +83,6 @@ fn m_setup(app: &mut app::App) -> FrameData {
 fn m_update(app: &app::App, _data: &mut FrameData, _ui: &egui::Context) {
     let gl = &app.gl;
     let shader = &_data.shader;
-    let vao = &_data.vao;
 
     glh::clear(gl, 0.2, 0.1, 0.1, 1.0);
     glh::set_viewport(
@@ -93,7 +102,15 @@ fn m_update(app: &app::App, _data: &mut FrameData, _ui: &egui::Context) {
         &glam::Mat4::IDENTITY,
     );
 
+    shader.set_uniform_mat4(
+        gl,
+        glh::StockShader::uniform_name_model_matrix(),
+        &glam::Mat4::IDENTITY,
+    );
+
     // update particles ----
+    let mut transforms: Vec<glam::Mat4> = Vec::new();
+    let mut instance_colors: Vec<glam::Vec4> = Vec::new();
     for p in &mut _data.particles {
         p.lifetime += 1.0;
         p.scale -= 0.001;
@@ -103,17 +120,20 @@ fn m_update(app: &app::App, _data: &mut FrameData, _ui: &egui::Context) {
 
         let mat =  glam::Mat4::from( glam::Affine3A::from_scale_rotation_translation( glam::vec3(p.scale, p.scale, p.scale), glam::Quat::from_axis_angle(glam::vec3(0.0, 0.0, 1.0), p.rotation), p.position) );  //glm::Mat4::identity();
 
-        shader.set_uniform_mat4(gl, glh::StockShader::uniform_name_model_matrix(), &mat);
+        transforms.push(mat);
 
         let green: f32 = 0.5 * (p.scale * 10.0);
         let red: f32 = 0.8 * (p.scale * 10.0);
         let blue: f32 = 1.0 - (app.input_state.mouse_pos.0 / 400.0);
-        shader.set_uniform_4f(
-            gl,
-            glh::StockShader::uniform_name_color(),
-            &[red, green, blue, 1.0],
-        );
-        vao.draw(gl);
+        instance_colors.push(glam::vec4(red, green, blue, 1.0));
+    }
+
+    // all particles in one draw call
+    let vao = &mut _data.vao;
+    vao.update_attrib(gl, glh::StockShader::attrib_name_instance_transform(), &transforms);
+    vao.update_attrib(gl, glh::StockShader::attrib_name_instance_color(), &instance_colors);
+    if !transforms.is_empty() {
+        vao.draw_instanced(gl, transforms.len() as i32);
     }
 
     shader.unbind(gl);
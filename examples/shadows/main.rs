@@ -0,0 +1,149 @@
+extern crate piralib;
+
+use glow::HasContext;
+use piralib::app;
+use piralib::egui;
+use piralib::event;
+use piralib::gl_helper as glh;
+use piralib::glow;
+use piralib::utils::camera::{Camera, OrbitCamera};
+use piralib::utils::geo::{Cuboid, Geometry};
+
+struct FrameData {
+    shader: glh::GlslProg,
+    cube_vao: glh::Vao,
+    shadow_map: glh::ShadowMap,
+    camera: OrbitCamera,
+
+    light_angle: f32,
+    pcf_radius: i32,
+}
+
+fn m_setup(app: &mut app::App) -> FrameData {
+    let gl = &app.gl;
+
+    let light_direction = glam::vec3(-0.4, -1.0, -0.6);
+    let shadow_map = glh::ShadowMap::directional(gl, light_direction);
+
+    let shader = glh::StockShader::new()
+        .lambert()
+        .receive_shadows(&shadow_map)
+        .build(gl);
+    let cube_vao = Cuboid::new_with_uniform_size(1.0)
+        .normals()
+        .get_vao(gl, &shader);
+
+    let aspect_ratio = app.input_state.window_size.0 as f32 / app.input_state.window_size.1 as f32;
+    let camera = OrbitCamera::new(aspect_ratio, 0.8, 0.1, 200.0);
+
+    FrameData {
+        shader,
+        cube_vao,
+        shadow_map,
+        camera,
+        light_angle: 0.6,
+        pcf_radius: 1,
+    }
+}
+
+// a ground plane and a ring of cubes
+fn model_matrices(time: f32) -> Vec<glam::Mat4> {
+    let mut matrices = vec![glam::Mat4::from_scale_rotation_translation(
+        glam::vec3(40.0, 0.1, 40.0),
+        glam::Quat::IDENTITY,
+        glam::vec3(0.0, -0.55, 0.0),
+    )];
+
+    for i in 0..12 {
+        let angle = i as f32 / 12.0 * std::f32::consts::TAU;
+        let height = 1.0 + (i % 3) as f32;
+        matrices.push(glam::Mat4::from_scale_rotation_translation(
+            glam::vec3(0.6, height, 0.6),
+            glam::Quat::from_rotation_y(angle + time),
+            glam::vec3(angle.cos() * 4.0, height * 0.5 - 0.5, angle.sin() * 4.0),
+        ));
+    }
+
+    matrices
+}
+
+fn m_event(app: &mut app::App, data: &mut FrameData, event: &event::WindowEvent) {
+    data.camera.handle_event(event, app);
+}
+
+fn m_update(app: &app::App, data: &mut FrameData, ui: &egui::Context) {
+    let gl = &app.gl;
+
+    egui::SidePanel::new(egui::panel::Side::Left, "shadows").show(ui, |ui| {
+        ui.label("light angle");
+        ui.add(egui::Slider::new(
+            &mut data.light_angle,
+            0.0..=std::f32::consts::TAU,
+        ));
+        ui.label("pcf radius");
+        ui.add(egui::Slider::new(&mut data.pcf_radius, 0..=3));
+    });
+
+    let camera = &mut data.camera;
+    camera.on_resize(app.get_window_size()[0], app.get_window_size()[1]);
+    camera.update();
+    let persp_matrix = camera.get_perspective_matrix();
+    let view_matrix = camera.get_view_matrix();
+
+    let light_direction = glam::vec3(data.light_angle.cos(), -1.0, data.light_angle.sin());
+    let settings = *data.shadow_map.get_settings();
+    data.shadow_map.set_light(glh::ShadowLight::Directional {
+        direction: light_direction,
+    });
+    data.shadow_map
+        .set_filtering(settings.bias, settings.normal_bias, data.pcf_radius);
+    data.shadow_map.update(&view_matrix, &persp_matrix);
+
+    let matrices = model_matrices(app.frame_number as f32 * 0.005);
+
+    // shadow pass
+    let casters: Vec<glh::ShadowCaster> = matrices
+        .iter()
+        .map(|matrix| glh::ShadowCaster::new(&data.cube_vao, *matrix))
+        .collect();
+    data.shadow_map.render(gl, &casters);
+
+    // color pass
+    glh::clear(gl, 0.8, 0.8, 0.8, 1.0);
+    unsafe {
+        gl.enable(glow::DEPTH_TEST);
+    }
+
+    let shader = &data.shader;
+    shader.bind(gl);
+    shader.set_uniform_mat4(
+        gl,
+        glh::StockShader::uniform_name_perspective_matrix(),
+        &persp_matrix,
+    );
+    shader.set_view_matrix(gl, &view_matrix);
+    shader.set_directional_light(gl, 0, light_direction, glam::Vec3::ONE);
+    data.shadow_map.apply(gl, shader);
+
+    for matrix in &matrices {
+        shader.set_model_matrix(gl, matrix);
+        data.cube_vao.draw(gl);
+    }
+    shader.unbind(gl);
+
+    unsafe {
+        gl.disable(glow::DEPTH_TEST);
+    }
+}
+
+fn main() {
+    app::AppBuilder::new(
+        app::AppSettings {
+            window_size: (1280, 720),
+            window_title: "shadows",
+        },
+        m_setup,
+    )
+    .event(m_event)
+    .run(m_update);
+}
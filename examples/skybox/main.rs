@@ -0,0 +1,130 @@
+extern crate piralib;
+
+use glow::HasContext;
+use piralib::app;
+use piralib::egui;
+use piralib::gl_helper as glh;
+use piralib::glow;
+
+use piralib::event;
+use piralib::utils::camera::{Camera, OrbitCamera};
+use piralib::utils::geo::{Cuboid, Geometry};
+use piralib::utils::skybox::Skybox;
+
+struct FrameData {
+    cube_shader: glh::GlslProg,
+    cube_vao: glh::Vao,
+    skybox: Skybox,
+    camera: OrbitCamera,
+}
+
+// a float sky gradient with a bright sun, laid out as an equirectangular panorama
+fn sky_panorama(width: u32, height: u32) -> image::DynamicImage {
+    let sun = glam::vec3(0.5, 0.4, -0.7).normalize();
+    let img = image::Rgb32FImage::from_fn(width, height, |x, y| {
+        let phi = (x as f32 / width as f32 - 0.5) * std::f32::consts::TAU;
+        let theta = y as f32 / height as f32 * std::f32::consts::PI;
+        let direction = glam::vec3(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        );
+
+        let horizon = glam::vec3(0.9, 0.8, 0.7);
+        let zenith = glam::vec3(0.2, 0.4, 0.9);
+        let ground = glam::vec3(0.25, 0.22, 0.2);
+        let mut color = if direction.y > 0.0 {
+            horizon.lerp(zenith, direction.y.sqrt())
+        } else {
+            horizon.lerp(ground, (-direction.y * 4.0).min(1.0))
+        };
+        color += glam::Vec3::splat(direction.dot(sun).max(0.0).powf(512.0) * 20.0);
+
+        image::Rgb([color.x, color.y, color.z])
+    });
+    image::DynamicImage::ImageRgb32F(img)
+}
+
+fn m_setup(app: &mut app::App) -> FrameData {
+    let gl = &app.gl;
+
+    let panorama = glh::Texture::from_image(
+        gl,
+        &sky_panorama(1024, 512),
+        glh::TextureSettings::default(),
+    )
+    .unwrap();
+    let cube_map = glh::Texture::cube_map_from_equirectangular(
+        gl,
+        &panorama,
+        512,
+        glh::TextureSettings::cube_map().mipmaps(),
+    )
+    .unwrap();
+    unsafe { gl.delete_texture(panorama.handle.unwrap()) };
+
+    let skybox = Skybox::new(gl, cube_map);
+
+    let (cube_vao, cube_shader) = Cuboid::new_with_uniform_size(0.5)
+        .normals()
+        .get_vao_and_shader(gl);
+
+    let aspect_ratio = app.input_state.window_size.0 as f32 / app.input_state.window_size.1 as f32;
+    let camera = OrbitCamera::new(aspect_ratio, 45.0, 0.01, 1000.0);
+
+    FrameData {
+        cube_shader,
+        cube_vao,
+        skybox,
+        camera,
+    }
+}
+
+fn m_event(app: &mut app::App, data: &mut FrameData, event: &event::WindowEvent) {
+    data.camera.handle_event(event, app);
+}
+
+fn m_update(app: &app::App, data: &mut FrameData, ui: &egui::Context) {
+    let gl = &app.gl;
+
+    egui::SidePanel::new(egui::panel::Side::Left, "skybox settings").show(ui, |ui| {
+        ui.add(egui::Slider::new(&mut data.skybox.intensity, 0.0..=2.0).text("intensity"));
+    });
+
+    let camera = &mut data.camera;
+    camera.on_resize(app.get_window_size()[0], app.get_window_size()[1]);
+    camera.update();
+
+    glh::clear(gl, 0.0, 0.0, 0.0, 1.0);
+    unsafe {
+        gl.enable(glow::DEPTH_TEST);
+    }
+
+    let cube_shader = &data.cube_shader;
+    cube_shader.bind(gl);
+    cube_shader.set_perspective_matrix(gl, &camera.get_perspective_matrix());
+    cube_shader.set_view_matrix(gl, &camera.get_view_matrix());
+    cube_shader.set_model_matrix(gl, &glam::Mat4::IDENTITY);
+    cube_shader.set_color(gl, &[1.0, 1.0, 1.0, 1.0]);
+    data.cube_vao.draw(gl);
+    cube_shader.unbind(gl);
+
+    // last, only fills the pixels nothing else was drawn to
+    data.skybox.draw_with_camera(gl, camera);
+
+    unsafe {
+        gl.disable(glow::DEPTH_TEST);
+    }
+}
+
+fn main() {
+    app::AppBuilder::new(
+        app::AppSettings {
+            window_size: (1920, 1080),
+            window_title: "skybox",
+        },
+        m_setup,
+    )
+    .event(m_event)
+    .run(m_update);
+}
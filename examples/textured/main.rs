@@ -26,8 +26,8 @@ fn m_setup(app: &mut app::App) -> FrameData {
 
     let img = image::open("assets/uv_image.png").unwrap().to_rgba8();
     println!("Image width: {:?} height: {:?}", img.width(), img.height());
-    let texture =
-        glh::Texture::new_from_image_rgbau8(gl, &img, glh::texture::TextureSettings::default());
+    let settings = glh::TextureSettings::default().mipmaps().anisotropy(8.0);
+    let texture = glh::Texture::new_from_image_rgbau8(gl, &img, settings);
 
     let mut img2 = image::RgbaImage::new(img.width(), img.height());
 
@@ -108,7 +108,7 @@ fn m_update(app: &app::App, data: &mut FrameData, _ui: &egui::Context) {
     glh::clear(gl, 0.2, 0.1, 0.1, 1.0);
 
     let _s_shader = glh::ScopedBind::new(gl, shader);
-    let _s_tex = glh::ScopedBind::new(gl, texture);
+    shader.bind_texture(gl, glh::StockShader::uniform_name_texture_sampler0(), texture);
 
     shader.set_orthographic_matrix(gl, &[app.input_state.window_size.0 as f32, app.input_state.window_size.1 as f32] );
 
@@ -0,0 +1,150 @@
+extern crate piralib;
+
+use piralib::app;
+use piralib::egui;
+use piralib::gl_helper as glh;
+
+// expands every line segment in to a screen aligned quad, needs GL 3.2+ ( not available on web )
+const VERTEX_SHADER: &str = "#version 400
+uniform mat4 uModelMatrix;
+uniform mat4 uPerspectiveMatrix;
+uniform mat4 uViewMatrix;
+
+in vec3 inPosition;
+in vec4 inColor;
+
+out vec4 vColor;
+void main()
+{
+    vColor = inColor;
+    gl_Position = uPerspectiveMatrix * uViewMatrix * uModelMatrix * vec4(inPosition, 1.0);
+}
+";
+
+const GEOMETRY_SHADER: &str = "#version 400
+layout(lines) in;
+layout(triangle_strip, max_vertices = 4) out;
+
+uniform vec2 uViewport;
+uniform float uThickness;
+
+in vec4 vColor[];
+out vec4 gColor;
+
+void main()
+{
+    vec2 p0 = gl_in[0].gl_Position.xy / gl_in[0].gl_Position.w;
+    vec2 p1 = gl_in[1].gl_Position.xy / gl_in[1].gl_Position.w;
+
+    // normal in pixels, scaled back to clip space
+    vec2 dir = normalize((p1 - p0) * uViewport);
+    vec2 offset = vec2(-dir.y, dir.x) * uThickness / uViewport;
+
+    gColor = vColor[0];
+    gl_Position = vec4(p0 + offset, 0.0, 1.0); EmitVertex();
+    gl_Position = vec4(p0 - offset, 0.0, 1.0); EmitVertex();
+    gColor = vColor[1];
+    gl_Position = vec4(p1 + offset, 0.0, 1.0); EmitVertex();
+    gl_Position = vec4(p1 - offset, 0.0, 1.0); EmitVertex();
+    EndPrimitive();
+}
+";
+
+const FRAG_SHADER: &str = "#version 400
+in vec4 gColor;
+
+out vec4 Color;
+void main()
+{
+    Color = gColor;
+}
+";
+
+struct FrameData {
+    shader: glh::GlslProg,
+    vao: glh::Vao,
+    thick_lines: bool,
+    thickness: f32,
+}
+
+fn m_setup(app: &mut app::App) -> FrameData {
+    let gl = &app.gl;
+
+    let mut vertices: Vec<f32> = Vec::new();
+    let mut colors: Vec<f32> = Vec::new();
+    let number_of_points = 200;
+    for i in 0..number_of_points {
+        let t = i as f32 / (number_of_points - 1) as f32;
+        vertices.append(&mut vec![
+            50.0 + t * 700.0,
+            300.0 + (t * std::f32::consts::PI * 6.0).sin() * 150.0,
+            0.0,
+        ]);
+        colors.append(&mut vec![t, 0.4, 1.0 - t, 1.0]);
+    }
+
+    // fall back to the stock shader when the context has no geometry shaders
+    let (shader, thick_lines) = match glh::GlslProg::builder()
+        .vertex(VERTEX_SHADER)
+        .geometry(GEOMETRY_SHADER)
+        .fragment(FRAG_SHADER)
+        .build(gl)
+    {
+        Ok(shader) => (shader, true),
+        Err(err) => {
+            println!("{}, drawing thin lines instead", err);
+            (glh::StockShader::new().color().build(gl), false)
+        }
+    };
+
+    let attribs = vec![
+        glh::VertexAttrib::new_position_attr_with_data(&vertices),
+        glh::VertexAttrib::new_color_attr_with_data(&colors),
+    ];
+    let vao = glh::Vao::new_from_attrib(gl, &attribs, glow::LINE_STRIP, &shader).unwrap();
+
+    FrameData {
+        shader,
+        vao,
+        thick_lines,
+        thickness: 8.0,
+    }
+}
+
+fn m_update(app: &app::App, data: &mut FrameData, ui: &egui::Context) {
+    let gl = &app.gl;
+    let window_size = app.get_window_size();
+
+    egui::SidePanel::new(egui::panel::Side::Left, "panel").show(ui, |ui| {
+        ui.label("thickness");
+        ui.add(egui::Slider::new(&mut data.thickness, 1.0..=40.0));
+    });
+
+    glh::clear(gl, 0.1, 0.1, 0.1, 1.0);
+
+    let shader = &data.shader;
+    shader.bind(gl);
+    shader.set_orthographic_matrix(gl, &window_size);
+    shader.set_view_matrix(gl, &glam::Mat4::IDENTITY);
+    shader.set_model_matrix(gl, &glam::Mat4::IDENTITY);
+
+    if data.thick_lines {
+        shader.set(gl, "uViewport", glam::Vec2::from(window_size));
+        shader.set(gl, "uThickness", data.thickness);
+    }
+
+    data.vao.draw(gl);
+
+    shader.unbind(gl);
+}
+
+fn main() {
+    app::AppBuilder::new(
+        app::AppSettings {
+            window_size: (800, 600),
+            window_title: "thick lines",
+        },
+        m_setup,
+    )
+    .run(m_update);
+}
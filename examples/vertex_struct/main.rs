@@ -0,0 +1,67 @@
+extern crate piralib;
+
+use piralib::app;
+use piralib::egui;
+use piralib::gl_helper as glh;
+use piralib::gl_helper::Vertex;
+
+// position and packed color interleaved in a single buffer
+#[repr(C)]
+#[derive(Vertex)]
+struct ColorVertex {
+    #[vertex(name = "inPosition")]
+    position: [f32; 3],
+    #[vertex(name = "inColor", normalized)]
+    color: [u8; 4],
+}
+
+struct FrameData {
+    shader: glh::GlslProg,
+    vao: glh::Vao,
+}
+
+fn m_setup(app: &mut app::App) -> FrameData {
+    let gl = &app.gl;
+
+    let vertices = vec![
+        ColorVertex { position: [0.0, 0.0, 0.0], color: [255, 0, 0, 255] },
+        ColorVertex { position: [400.0, 0.0, 0.0], color: [0, 255, 0, 255] },
+        ColorVertex { position: [400.0, 400.0, 0.0], color: [0, 0, 255, 255] },
+        ColorVertex { position: [0.0, 400.0, 0.0], color: [255, 255, 255, 255] },
+    ];
+    let indices = vec![0, 1, 2, 0, 2, 3];
+
+    let shader = glh::StockShader::new().color().build(gl);
+    let vao =
+        glh::Vao::new_from_vertices_indexed(gl, &vertices, &indices, glow::TRIANGLES, &shader)
+            .unwrap();
+
+    FrameData { shader, vao }
+}
+
+fn m_update(app: &app::App, data: &mut FrameData, _ui: &egui::Context) {
+    let gl = &app.gl;
+    let shader = &data.shader;
+
+    glh::clear(gl, 0.2, 0.2, 0.2, 1.0);
+
+    shader.bind(gl);
+    shader.set_orthographic_matrix(gl, &app.get_window_size());
+    shader.set_view_matrix(gl, &glam::Mat4::IDENTITY);
+    shader.set_model_matrix(gl, &glam::Mat4::from_translation(glam::vec3(50.0, 50.0, 0.0)));
+
+    data.vao.draw(gl);
+
+    shader.unbind(gl);
+}
+
+fn main() {
+    app::AppBuilder::new(
+        app::AppSettings {
+            window_size: (500, 500),
+            window_title: "vertex struct",
+        },
+        m_setup,
+    )
+    .run(m_update);
+}
@@ -0,0 +1,246 @@
+//! `#[derive(Vertex)]` for `#[repr(C)]` vertex structs used with `piralib::gl_helper::Vao`.
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(Vertex)]
+//! struct MyVertex {
+//!     #[vertex(name = "inPosition")]
+//!     position: [f32; 3],
+//!     #[vertex(name = "inColor", normalized)]
+//!     color: [u8; 4],
+//!     #[vertex(integer, location = 6)]
+//!     id: u32,
+//!     #[vertex(skip)]
+//!     _padding: u32,
+//! }
+//! ```
+//!
+//! Each field becomes one attribute of the generated `VertexLayout`, the component type and count
+//! come from the field type's `VertexComponent` implementation and the offset from `offset_of!`,
+//! which needs Rust 1.77 in the crate using the derive.
+//! The struct is uploaded as its raw bytes, so it also gets a `VertexData` implementation and
+//! fails to compile if its fields don't add up to its size. Fill padding with `skip` fields.
+//! Matrix fields ( `glam::Mat4`, `[[f32; 4]; 4]`, ... ) take one location per column.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    name: Option<String>,
+    normalized: bool,
+    integer: bool,
+    location: Option<u32>,
+    skip: bool,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    if !is_repr_c(input) {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "#[derive(Vertex)] requires #[repr(C)] so the field offsets match the gpu layout",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "#[derive(Vertex)] only supports structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "#[derive(Vertex)] only supports structs",
+            ))
+        }
+    };
+
+    // the padding check is a const item, which can't use the struct's generic parameters
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "#[derive(Vertex)] doesn't support generic structs",
+        ));
+    }
+
+    let ident = &input.ident;
+    let mut attribs = Vec::new();
+    let mut integer_checks = Vec::new();
+    let field_types: Vec<&syn::Type> = fields.iter().map(|field| &field.ty).collect();
+
+    for field in fields {
+        let options = parse_field_options(field)?;
+        if options.skip {
+            continue;
+        }
+
+        let field_ident = field.ident.as_ref().unwrap();
+        let field_ty = &field.ty;
+        let name = options.name.unwrap_or_else(|| field_ident.to_string());
+
+        let normalized = if options.normalized {
+            quote! { .normalized() }
+        } else {
+            quote! {}
+        };
+        let integer = if options.integer {
+            let message = format!(
+                "integer vertex attribute {} needs an integer field type",
+                name
+            );
+            integer_checks.push(quote_spanned! {field_ty.span()=>
+                assert!(
+                    !<#field_ty as ::piralib::gl_helper::VertexComponent>::COMPONENT_TYPE.is_float(),
+                    #message
+                );
+            });
+            quote! { .integer() }
+        } else {
+            quote! {}
+        };
+        let location = match options.location {
+            Some(loc) => quote! { .location(#loc) },
+            None => quote! {},
+        };
+
+        attribs.push(quote! {
+            let layout = layout.push_attrib(
+                ::piralib::gl_helper::LayoutAttrib::new(
+                    #name,
+                    <#field_ty as ::piralib::gl_helper::VertexComponent>::SIZE,
+                    <#field_ty as ::piralib::gl_helper::VertexComponent>::COMPONENT_TYPE,
+                    ::core::mem::offset_of!(#ident, #field_ident) as i32,
+                )
+                .columns(<#field_ty as ::piralib::gl_helper::VertexComponent>::COLUMNS)
+                #normalized
+                #integer
+                #location
+            );
+        });
+    }
+
+    let padding_error = format!("{} has padding, fill it with #[vertex(skip)] fields", ident);
+
+    Ok(quote! {
+        const _: () = {
+            fn assert_vertex_data<T: ::piralib::gl_helper::VertexData>() {}
+            fn assert_fields() {
+                #(assert_vertex_data::<#field_types>();)*
+            }
+            assert!(
+                ::core::mem::size_of::<#ident>() == 0 #(+ ::core::mem::size_of::<#field_types>())*,
+                #padding_error
+            );
+            #(#integer_checks)*
+        };
+
+        // fields are plain data and fill the whole struct, checked above
+        unsafe impl ::piralib::gl_helper::VertexData for #ident {}
+
+        impl ::piralib::gl_helper::Vertex for #ident {
+            fn layout() -> ::piralib::gl_helper::VertexLayout {
+                let layout = ::piralib::gl_helper::VertexLayout::new();
+                #(#attribs)*
+                layout.stride(::core::mem::size_of::<#ident>() as i32)
+            }
+        }
+    })
+}
+
+fn is_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attr| {
+        if !attr.path.is_ident("repr") {
+            return false;
+        }
+        match attr.parse_meta() {
+            Ok(Meta::List(list)) => list.nested.iter().any(|nested| match nested {
+                NestedMeta::Meta(Meta::Path(path)) => path.is_ident("C"),
+                _ => false,
+            }),
+            _ => false,
+        }
+    })
+}
+
+fn parse_field_options(field: &syn::Field) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+
+    for attr in &field.attrs {
+        if !attr.path.is_ident("vertex") {
+            continue;
+        }
+
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "expected #[vertex(...)] with a list of options",
+                ))
+            }
+        };
+
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("normalized") => {
+                    options.normalized = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("integer") => {
+                    options.integer = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    options.skip = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("name") => {
+                    match &name_value.lit {
+                        Lit::Str(name) => options.name = Some(name.value()),
+                        lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(name_value))
+                    if name_value.path.is_ident("location") =>
+                {
+                    match &name_value.lit {
+                        Lit::Int(loc) => options.location = Some(loc.base10_parse()?),
+                        lit => return Err(syn::Error::new_spanned(lit, "expected an integer")),
+                    }
+                }
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        "unknown vertex option, expected one of: name, normalized, integer, location, skip",
+                    ))
+                }
+            }
+        }
+    }
+
+    if options.normalized && options.integer {
+        return Err(syn::Error::new_spanned(
+            field,
+            "a vertex attribute can't be both normalized and integer",
+        ));
+    }
+
+    Ok(options)
+}
@@ -0,0 +1,439 @@
+use crate::gl_helper::texture::{backend_compatible, pixel_size, upload_format, TextureSettings};
+use crate::gl_helper::texture_error::TextureError;
+use crate::gl_helper::{Bindable, Texture};
+use glow::{self, HasContext};
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+// ~4MB, a quarter of a 4K RGBA8 image
+const DEFAULT_BYTES_PER_FRAME: usize = 4 * 1024 * 1024;
+
+enum Source {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+struct Upload {
+    texture: Texture,
+    pixels: Vec<u8>,
+    row_bytes: usize,
+    next_row: i32,      // first row not yet copied out of `pixels`
+    uploaded_rows: i32, // rows in the texture
+    #[cfg(not(target_arch = "wasm32"))]
+    pbos: [glow::Buffer; 2],
+    #[cfg(not(target_arch = "wasm32"))]
+    staged: Option<Staged>,
+}
+
+// rows written in to one of the pixel buffers, copied in to the texture the frame after
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy)]
+struct Staged {
+    pbo: usize,
+    first_row: i32,
+    rows: i32,
+}
+
+enum LoadState {
+    Decoding(mpsc::Receiver<Result<image::DynamicImage, TextureError>>),
+    #[cfg(target_arch = "wasm32")]
+    Pending(Source),
+    Uploading(Box<Upload>),
+    Ready(Texture),
+    Failed(TextureError),
+}
+
+/// A texture decoded on a background thread and uploaded a few rows per frame. On native the
+/// rows go through two pixel unpack buffers, one is filled while the other is copied in to
+/// the texture. Call `update` once per frame, `get_texture` is a placeholder until `is_ready`.
+///
+/// ```ignore
+/// let mut photo = glh::AsyncTexture::from_path(gl, "assets/4k.jpg", glh::TextureSettings::default());
+/// // every frame
+/// photo.update(gl);
+/// shader.bind_texture(gl, "uTexture", photo.get_texture());
+/// ```
+pub struct AsyncTexture {
+    placeholder: Texture,
+    settings: TextureSettings,
+    bytes_per_frame: usize,
+    state: LoadState,
+}
+
+impl AsyncTexture {
+    // decodes any image the image crate can open, like Texture::from_path
+    pub fn from_path<P: Into<PathBuf>>(
+        gl: &glow::Context,
+        path: P,
+        settings: TextureSettings,
+    ) -> Self {
+        Self::new(gl, Source::Path(path.into()), settings)
+    }
+
+    // encoded image data, like Texture::from_bytes
+    pub fn from_bytes(gl: &glow::Context, bytes: Vec<u8>, settings: TextureSettings) -> Self {
+        Self::new(gl, Source::Bytes(bytes), settings)
+    }
+
+    // already decoded, only the upload is spread over frames
+    pub fn from_image(
+        gl: &glow::Context,
+        img: image::DynamicImage,
+        settings: TextureSettings,
+    ) -> Self {
+        let mut texture = Self {
+            placeholder: create_placeholder(gl),
+            settings,
+            bytes_per_frame: DEFAULT_BYTES_PER_FRAME,
+            state: LoadState::Failed(TextureError::Aborted(String::new())),
+        };
+        texture.state = texture.start_upload(gl, Ok(img));
+        texture
+    }
+
+    fn new(gl: &glow::Context, source: Source, settings: TextureSettings) -> Self {
+        Self {
+            placeholder: create_placeholder(gl),
+            settings,
+            bytes_per_frame: DEFAULT_BYTES_PER_FRAME,
+            state: spawn_decode(source),
+        }
+    }
+
+    // upload budget, lower it if the uploads still show up in the frame time
+    pub fn bytes_per_frame(mut self, bytes: usize) -> Self {
+        self.bytes_per_frame = bytes.max(1);
+        self
+    }
+
+    pub fn is_ready(&self) -> bool {
+        matches!(self.state, LoadState::Ready(_))
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self.state, LoadState::Failed(_))
+    }
+
+    pub fn get_error(&self) -> Option<&TextureError> {
+        match &self.state {
+            LoadState::Failed(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    // the loaded texture, or the placeholder while loading or after a failure
+    pub fn get_texture(&self) -> &Texture {
+        match &self.state {
+            LoadState::Ready(texture) => texture,
+            _ => &self.placeholder,
+        }
+    }
+
+    // 0 while decoding, the uploaded fraction after that
+    pub fn get_progress(&self) -> f32 {
+        match &self.state {
+            LoadState::Ready(_) => 1.0,
+            LoadState::Uploading(upload) => {
+                upload.uploaded_rows as f32 / upload.texture.height as f32
+            }
+            _ => 0.0,
+        }
+    }
+
+    // advances the load by one step, call it once per frame
+    pub fn update(&mut self, gl: &glow::Context) {
+        // moved out so the receiver and upload can be consumed
+        let state = std::mem::replace(
+            &mut self.state,
+            LoadState::Failed(TextureError::Aborted(String::new())),
+        );
+        self.state = match state {
+            LoadState::Decoding(receiver) => match receiver.try_recv() {
+                Ok(result) => self.start_upload(gl, result),
+                Err(mpsc::TryRecvError::Empty) => LoadState::Decoding(receiver),
+                Err(mpsc::TryRecvError::Disconnected) => LoadState::Failed(TextureError::Aborted(
+                    "the decoder thread panicked".to_string(),
+                )),
+            },
+            // no threads on the web, the decode happens on the first update
+            #[cfg(target_arch = "wasm32")]
+            LoadState::Pending(source) => self.start_upload(gl, decode(source)),
+            LoadState::Uploading(mut upload) => {
+                if self.upload_rows(gl, &mut upload) {
+                    finish_upload(gl, &upload);
+                    LoadState::Ready(upload.texture)
+                } else {
+                    LoadState::Uploading(upload)
+                }
+            }
+            state => state,
+        };
+    }
+
+    fn start_upload(
+        &self,
+        gl: &glow::Context,
+        result: Result<image::DynamicImage, TextureError>,
+    ) -> LoadState {
+        let img = match result {
+            Ok(img) => img,
+            Err(err) => return LoadState::Failed(err),
+        };
+        let img = backend_compatible(&img).unwrap_or(img);
+
+        let (internal_format, format, data_type) = match upload_format(&img, self.settings.srgb) {
+            Some(format) => format,
+            None => {
+                return LoadState::Failed(TextureError::UnsupportedFormat(format!(
+                    "{:?}",
+                    img.color()
+                )))
+            }
+        };
+        let mut settings = self.settings;
+        settings.internal_format = internal_format;
+        settings.format = format;
+        settings.data_type = data_type;
+
+        // allocated up front, mipmaps are generated once every row is in
+        let generate_mipmaps = settings.mipmaps;
+        settings.mipmaps = false;
+        let mut texture =
+            Texture::new_from_data(gl, None, img.width() as i32, img.height() as i32, settings);
+        texture.settings.mipmaps = generate_mipmaps;
+
+        let row_bytes = pixel_size(format, data_type).unwrap() * img.width() as usize;
+
+        LoadState::Uploading(Box::new(Upload {
+            texture,
+            pixels: img.into_bytes(),
+            row_bytes,
+            next_row: 0,
+            uploaded_rows: 0,
+            #[cfg(not(target_arch = "wasm32"))]
+            pbos: unsafe {
+                [
+                    gl.create_buffer().expect("could not create pixel buffer"),
+                    gl.create_buffer().expect("could not create pixel buffer"),
+                ]
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            staged: None,
+        }))
+    }
+
+    // the first row and number of rows of this frame's budget, advances next_row past them
+    fn next_rows(&self, upload: &mut Upload) -> (i32, i32) {
+        let first_row = upload.next_row;
+        let rows = rows_per_frame(upload.row_bytes, self.bytes_per_frame)
+            .min(upload.texture.height - first_row);
+        upload.next_row += rows;
+        (first_row, rows)
+    }
+
+    // copies the rows staged last frame in to the texture and stages the next ones in the
+    // other buffer, so the transfer has a frame to finish before the texture reads it.
+    // true once every row is uploaded
+    #[cfg(not(target_arch = "wasm32"))]
+    fn upload_rows(&self, gl: &glow::Context, upload: &mut Upload) -> bool {
+        upload.texture.bind(gl);
+        unsafe {
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+
+            let staged = upload.staged.take();
+            if let Some(staged) = staged {
+                gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(upload.pbos[staged.pbo]));
+                gl.tex_sub_image_2d(
+                    upload.texture.settings.target,
+                    0,
+                    0,
+                    staged.first_row,
+                    upload.texture.width,
+                    staged.rows,
+                    upload.texture.settings.format,
+                    upload.texture.settings.data_type,
+                    glow::PixelUnpackData::BufferOffset(0),
+                );
+                upload.uploaded_rows += staged.rows;
+            }
+
+            if upload.next_row < upload.texture.height {
+                let pbo = staged.map_or(0, |staged| 1 - staged.pbo);
+                let (first_row, rows) = self.next_rows(upload);
+                let start = first_row as usize * upload.row_bytes;
+                let chunk = &upload.pixels[start..start + rows as usize * upload.row_bytes];
+
+                gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(upload.pbos[pbo]));
+                // new storage, the old one may still be read by the transfer of two frames ago
+                gl.buffer_data_size(
+                    glow::PIXEL_UNPACK_BUFFER,
+                    chunk.len() as i32,
+                    glow::STREAM_DRAW,
+                );
+                let mapped = gl.map_buffer_range(
+                    glow::PIXEL_UNPACK_BUFFER,
+                    0,
+                    chunk.len() as i32,
+                    glow::MAP_WRITE_BIT | glow::MAP_INVALIDATE_BUFFER_BIT,
+                );
+                if mapped.is_null() {
+                    gl.buffer_sub_data_u8_slice(glow::PIXEL_UNPACK_BUFFER, 0, chunk);
+                } else {
+                    std::ptr::copy_nonoverlapping(chunk.as_ptr(), mapped, chunk.len());
+                    gl.unmap_buffer(glow::PIXEL_UNPACK_BUFFER);
+                }
+                upload.staged = Some(Staged {
+                    pbo,
+                    first_row,
+                    rows,
+                });
+            }
+
+            gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, None);
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+        }
+        upload.texture.unbind(gl);
+
+        upload.uploaded_rows >= upload.texture.height
+    }
+
+    // WebGL2 can't map buffers, the rows go straight from memory
+    #[cfg(target_arch = "wasm32")]
+    fn upload_rows(&self, gl: &glow::Context, upload: &mut Upload) -> bool {
+        let (first_row, rows) = self.next_rows(upload);
+        let start = first_row as usize * upload.row_bytes;
+        let chunk = &upload.pixels[start..start + rows as usize * upload.row_bytes];
+
+        upload.texture.bind(gl);
+        unsafe {
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.tex_sub_image_2d(
+                upload.texture.settings.target,
+                0,
+                0,
+                first_row,
+                upload.texture.width,
+                rows,
+                upload.texture.settings.format,
+                upload.texture.settings.data_type,
+                glow::PixelUnpackData::Slice(chunk),
+            );
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+        }
+        upload.texture.unbind(gl);
+
+        upload.uploaded_rows += rows;
+        upload.uploaded_rows >= upload.texture.height
+    }
+
+    pub fn delete(&mut self, gl: &glow::Context) {
+        unsafe {
+            if let Some(handle) = self.placeholder.handle.take() {
+                gl.delete_texture(handle);
+            }
+            match &mut self.state {
+                LoadState::Ready(texture) => {
+                    if let Some(handle) = texture.handle.take() {
+                        gl.delete_texture(handle);
+                    }
+                }
+                LoadState::Uploading(upload) => {
+                    delete_pbos(gl, upload);
+                    if let Some(handle) = upload.texture.handle.take() {
+                        gl.delete_texture(handle);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.state =
+            LoadState::Failed(TextureError::Aborted("the texture was deleted".to_string()));
+    }
+}
+
+fn finish_upload(gl: &glow::Context, upload: &Upload) {
+    delete_pbos(gl, upload);
+    if upload.texture.settings.mipmaps {
+        upload.texture.bind(gl);
+        unsafe { gl.generate_mipmap(upload.texture.settings.target) };
+        upload.texture.unbind(gl);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn delete_pbos(gl: &glow::Context, upload: &Upload) {
+    unsafe {
+        for pbo in upload.pbos.iter() {
+            gl.delete_buffer(*pbo);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn delete_pbos(_gl: &glow::Context, _upload: &Upload) {}
+
+// mid grey, so the loading texture doesn't flash
+fn create_placeholder(gl: &glow::Context) -> Texture {
+    Texture::new_from_data(
+        gl,
+        Some(&[128, 128, 128, 255]),
+        1,
+        1,
+        TextureSettings::default(),
+    )
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_decode(source: Source) -> LoadState {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        // the receiver is gone if the texture was dropped while decoding
+        let _ = sender.send(decode(source));
+    });
+    LoadState::Decoding(receiver)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn spawn_decode(source: Source) -> LoadState {
+    LoadState::Pending(source)
+}
+
+fn decode(source: Source) -> Result<image::DynamicImage, TextureError> {
+    let img = match source {
+        Source::Path(path) => image::open(path)?,
+        Source::Bytes(bytes) => image::load_from_memory(&bytes)?,
+    };
+    Ok(img)
+}
+
+// at least one row, even if a single row is over the budget
+fn rows_per_frame(row_bytes: usize, bytes_per_frame: usize) -> i32 {
+    (bytes_per_frame / row_bytes.max(1)).clamp(1, i32::MAX as usize) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_fit_the_budget() {
+        // a 4K RGBA8 row is 15360 bytes
+        assert_eq!(rows_per_frame(3840 * 4, DEFAULT_BYTES_PER_FRAME), 273);
+        assert_eq!(rows_per_frame(3840 * 4, 100), 1);
+    }
+
+    #[test]
+    fn decodes_encoded_bytes() {
+        let mut bytes = Vec::new();
+        image::DynamicImage::new_rgb8(3, 2)
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+
+        let img = decode(Source::Bytes(bytes)).unwrap();
+        assert_eq!((img.width(), img.height()), (3, 2));
+        assert!(decode(Source::Bytes(vec![1, 2, 3])).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::gl_helper::compressed_texture::CompressedFormat;
+
+// channels of the decoded pixels, None for formats without a CPU decoder
+pub fn decoded_channels(format: CompressedFormat) -> Option<usize> {
+    match format {
+        CompressedFormat::Bc1 { .. } | CompressedFormat::Bc2 | CompressedFormat::Bc3 => Some(4),
+        CompressedFormat::Bc4 { signed: false } => Some(1),
+        CompressedFormat::Bc5 { signed: false } => Some(2),
+        _ => None,
+    }
+}
+
+// decodes a BC1 - BC5 level to tightly packed 8 bit pixels, RGBA, R or RG.
+// None if the format has no decoder or the data is too short
+pub fn decompress(
+    format: CompressedFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Option<Vec<u8>> {
+    let channels = decoded_channels(format)?;
+    if data.len() < format.level_size(width, height) {
+        return None;
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    let block_size = format.bytes_per_block();
+
+    let mut pixels = vec![0u8; width * height * channels];
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let offset = (block_y * blocks_x + block_x) * block_size;
+            let block = &data[offset..offset + block_size];
+            let texels = decode_block(format, block);
+
+            // blocks at the right and bottom edges can be partially outside the image
+            for y in 0..4.min(height - block_y * 4) {
+                for x in 0..4.min(width - block_x * 4) {
+                    let pixel = ((block_y * 4 + y) * width + block_x * 4 + x) * channels;
+                    pixels[pixel..pixel + channels].copy_from_slice(&texels[y * 4 + x][..channels]);
+                }
+            }
+        }
+    }
+    Some(pixels)
+}
+
+fn decode_block(format: CompressedFormat, block: &[u8]) -> [[u8; 4]; 16] {
+    match format {
+        CompressedFormat::Bc1 { alpha } => decode_color(block, false, alpha),
+        CompressedFormat::Bc2 => {
+            let mut texels = decode_color(&block[8..16], true, false);
+            let mut alpha = [0u8; 8];
+            alpha.copy_from_slice(&block[0..8]);
+            let bits = u64::from_le_bytes(alpha);
+            for (i, texel) in texels.iter_mut().enumerate() {
+                texel[3] = ((bits >> (4 * i)) & 0xF) as u8 * 17;
+            }
+            texels
+        }
+        CompressedFormat::Bc3 => {
+            let mut texels = decode_color(&block[8..16], true, false);
+            let alpha = decode_alpha(&block[0..8]);
+            for (texel, alpha) in texels.iter_mut().zip(alpha.iter()) {
+                texel[3] = *alpha;
+            }
+            texels
+        }
+        CompressedFormat::Bc4 { .. } => {
+            let red = decode_alpha(&block[0..8]);
+            let mut texels = [[0, 0, 0, 255]; 16];
+            for (texel, red) in texels.iter_mut().zip(red.iter()) {
+                texel[0] = *red;
+            }
+            texels
+        }
+        CompressedFormat::Bc5 { .. } => {
+            let red = decode_alpha(&block[0..8]);
+            let green = decode_alpha(&block[8..16]);
+            let mut texels = [[0, 0, 0, 255]; 16];
+            for (i, texel) in texels.iter_mut().enumerate() {
+                texel[0] = red[i];
+                texel[1] = green[i];
+            }
+            texels
+        }
+        _ => unreachable!("no CPU decoder for {:?}", format),
+    }
+}
+
+fn rgb565(color: u16) -> [u32; 3] {
+    let r = (color >> 11) as u32 & 31;
+    let g = (color >> 5) as u32 & 63;
+    let b = color as u32 & 31;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+// the BC1 color block, also used by BC2 and BC3 which are always in four color mode.
+// BC1 switches to three colors when color0 <= color1, index 3 is then black,
+// transparent only for the punch through alpha variant
+fn decode_color(block: &[u8], four_color_only: bool, punch_through: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let c0 = rgb565(color0);
+    let c1 = rgb565(color1);
+    let mix = |weight0: u32, weight1: u32| {
+        let total = weight0 + weight1;
+        [
+            ((c0[0] * weight0 + c1[0] * weight1) / total) as u8,
+            ((c0[1] * weight0 + c1[1] * weight1) / total) as u8,
+            ((c0[2] * weight0 + c1[2] * weight1) / total) as u8,
+            255,
+        ]
+    };
+
+    let palette = if color0 > color1 || four_color_only {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        let black = if punch_through { 0 } else { 255 };
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0, 0, 0, black]]
+    };
+
+    let mut texels = [[0u8; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+    texels
+}
+
+// the BC3 alpha block, BC4 and BC5 use it for their channels
+fn decode_alpha(block: &[u8]) -> [u8; 16] {
+    let a0 = block[0] as u32;
+    let a1 = block[1] as u32;
+
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i as usize + 1] = (((7 - i) * a0 + i * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i as usize + 1] = (((5 - i) * a0 + i * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bits = 0u64;
+    for (i, byte) in block[2..8].iter().enumerate() {
+        bits |= (*byte as u64) << (8 * i);
+    }
+
+    let mut values = [0u8; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((bits >> (3 * i)) & 7) as usize];
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: u16 = 0xF800;
+    const BLUE: u16 = 0x001F;
+
+    fn color_block(color0: u16, color1: u16, indices: u32) -> Vec<u8> {
+        let mut block = color0.to_le_bytes().to_vec();
+        block.extend_from_slice(&color1.to_le_bytes());
+        block.extend_from_slice(&indices.to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn bc1_colors() {
+        // every texel picks color1
+        let block = color_block(RED, BLUE, 0x5555_5555);
+        let pixels = decompress(CompressedFormat::Bc1 { alpha: false }, 4, 4, &block).unwrap();
+        assert_eq!(&pixels[0..4], &[0, 0, 255, 255]);
+        assert_eq!(&pixels[60..64], &[0, 0, 255, 255]);
+
+        // color0 <= color1 with punch through, index 3 is transparent
+        let block = color_block(BLUE, RED, 0xFFFF_FFFF);
+        let pixels = decompress(CompressedFormat::Bc1 { alpha: true }, 4, 4, &block).unwrap();
+        assert_eq!(&pixels[0..4], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bc1_rgb_three_color_mode() {
+        // color0 <= color1 without alpha, index 2 is the average and index 3 opaque black
+        let block = color_block(BLUE, RED, 0x0000_00FE);
+        let pixels = decompress(CompressedFormat::Bc1 { alpha: false }, 4, 4, &block).unwrap();
+        assert_eq!(&pixels[0..4], &[127, 0, 127, 255]);
+        assert_eq!(&pixels[4..8], &[0, 0, 0, 255]);
+        assert_eq!(&pixels[12..16], &[0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc3_alpha_and_cropping() {
+        // alpha 255 for the first texel, 0 for the rest
+        let mut block = vec![255u8, 0, 0x48, 0x92, 0x24, 0x49, 0x92, 0x24];
+        block.extend(color_block(RED, RED, 0));
+        let pixels = decompress(CompressedFormat::Bc3, 2, 2, &block).unwrap();
+        assert_eq!(pixels.len(), 2 * 2 * 4);
+        assert_eq!(&pixels[0..4], &[255, 0, 0, 255]);
+        assert_eq!(pixels[7], 0);
+
+        assert!(decompress(CompressedFormat::Bc3, 8, 8, &block).is_none());
+        assert!(decompress(CompressedFormat::Bc7, 4, 4, &[0u8; 16]).is_none());
+    }
+
+    #[test]
+    fn bc4_interpolates() {
+        // index 2 is 6/7 of red0 + 1/7 of red1
+        let block = [140u8, 0, 0b0000_0010, 0, 0, 0, 0, 0];
+        let pixels = decompress(CompressedFormat::Bc4 { signed: false }, 1, 1, &block).unwrap();
+        assert_eq!(pixels, vec![120]);
+    }
+}
@@ -0,0 +1,482 @@
+use crate::gl_helper::bc_decoder;
+use crate::gl_helper::texture::TextureSettings;
+use crate::gl_helper::texture_container;
+use crate::gl_helper::texture_error::TextureError;
+use crate::gl_helper::Texture;
+use glow::{self, HasContext};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Block compressed formats found in KTX2 and DDS containers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressedFormat {
+    Bc1 { alpha: bool }, // DXT1, alpha is the 1 bit punch through mode
+    Bc2,                 // DXT3
+    Bc3,                 // DXT5
+    Bc4 { signed: bool },
+    Bc5 { signed: bool },
+    Bc6h { signed: bool },
+    Bc7,
+    Etc2Rgb8,
+    Etc2Rgb8A1,
+    Etc2Rgba8,
+    EacR11 { signed: bool },
+    EacRg11 { signed: bool },
+    Astc { block_width: u32, block_height: u32 },
+}
+
+impl CompressedFormat {
+    pub fn block_size(&self) -> (u32, u32) {
+        match self {
+            CompressedFormat::Astc {
+                block_width,
+                block_height,
+            } => (*block_width, *block_height),
+            _ => (4, 4),
+        }
+    }
+
+    pub fn bytes_per_block(&self) -> usize {
+        match self {
+            CompressedFormat::Bc1 { .. }
+            | CompressedFormat::Bc4 { .. }
+            | CompressedFormat::Etc2Rgb8
+            | CompressedFormat::Etc2Rgb8A1
+            | CompressedFormat::EacR11 { .. } => 8,
+            _ => 16,
+        }
+    }
+
+    // bytes of a width x height level, partial blocks at the edges take a whole block
+    pub fn level_size(&self, width: u32, height: u32) -> usize {
+        let (block_width, block_height) = self.block_size();
+        let blocks_x = width.div_ceil(block_width).max(1) as usize;
+        let blocks_y = height.div_ceil(block_height).max(1) as usize;
+        blocks_x * blocks_y * self.bytes_per_block()
+    }
+
+    // None for sRGB variants that don't exist, ie: sRGB BC4
+    pub fn gl_internal_format(&self, srgb: bool) -> Option<u32> {
+        let format = match (self, srgb) {
+            (CompressedFormat::Bc1 { alpha: false }, false) => glow::COMPRESSED_RGB_S3TC_DXT1_EXT,
+            (CompressedFormat::Bc1 { alpha: false }, true) => glow::COMPRESSED_SRGB_S3TC_DXT1_EXT,
+            (CompressedFormat::Bc1 { alpha: true }, false) => glow::COMPRESSED_RGBA_S3TC_DXT1_EXT,
+            (CompressedFormat::Bc1 { alpha: true }, true) => {
+                glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT
+            }
+            (CompressedFormat::Bc2, false) => glow::COMPRESSED_RGBA_S3TC_DXT3_EXT,
+            (CompressedFormat::Bc2, true) => glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT,
+            (CompressedFormat::Bc3, false) => glow::COMPRESSED_RGBA_S3TC_DXT5_EXT,
+            (CompressedFormat::Bc3, true) => glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT,
+            (CompressedFormat::Bc4 { signed: false }, false) => glow::COMPRESSED_RED_RGTC1,
+            (CompressedFormat::Bc4 { signed: true }, false) => glow::COMPRESSED_SIGNED_RED_RGTC1,
+            (CompressedFormat::Bc5 { signed: false }, false) => glow::COMPRESSED_RG_RGTC2,
+            (CompressedFormat::Bc5 { signed: true }, false) => glow::COMPRESSED_SIGNED_RG_RGTC2,
+            (CompressedFormat::Bc6h { signed: false }, false) => {
+                glow::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT
+            }
+            (CompressedFormat::Bc6h { signed: true }, false) => {
+                glow::COMPRESSED_RGB_BPTC_SIGNED_FLOAT
+            }
+            (CompressedFormat::Bc7, false) => glow::COMPRESSED_RGBA_BPTC_UNORM,
+            (CompressedFormat::Bc7, true) => glow::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+            (CompressedFormat::Etc2Rgb8, false) => glow::COMPRESSED_RGB8_ETC2,
+            (CompressedFormat::Etc2Rgb8, true) => glow::COMPRESSED_SRGB8_ETC2,
+            (CompressedFormat::Etc2Rgb8A1, false) => glow::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            (CompressedFormat::Etc2Rgb8A1, true) => glow::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            (CompressedFormat::Etc2Rgba8, false) => glow::COMPRESSED_RGBA8_ETC2_EAC,
+            (CompressedFormat::Etc2Rgba8, true) => glow::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+            (CompressedFormat::EacR11 { signed: false }, false) => glow::COMPRESSED_R11_EAC,
+            (CompressedFormat::EacR11 { signed: true }, false) => glow::COMPRESSED_SIGNED_R11_EAC,
+            (CompressedFormat::EacRg11 { signed: false }, false) => glow::COMPRESSED_RG11_EAC,
+            (CompressedFormat::EacRg11 { signed: true }, false) => glow::COMPRESSED_SIGNED_RG11_EAC,
+            (
+                CompressedFormat::Astc {
+                    block_width,
+                    block_height,
+                },
+                srgb,
+            ) => return astc_internal_format(*block_width, *block_height, srgb),
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    // any one of these extensions enables the format, an empty list means it is core
+    pub fn required_extensions(&self, srgb: bool) -> &'static [&'static str] {
+        match self {
+            CompressedFormat::Bc1 { .. } | CompressedFormat::Bc2 | CompressedFormat::Bc3
+                if srgb =>
+            {
+                &[
+                    "GL_EXT_texture_sRGB",
+                    "GL_EXT_texture_compression_s3tc_srgb",
+                    "WEBGL_compressed_texture_s3tc_srgb",
+                ]
+            }
+            CompressedFormat::Bc1 { .. } | CompressedFormat::Bc2 | CompressedFormat::Bc3 => &[
+                "GL_EXT_texture_compression_s3tc",
+                "WEBGL_compressed_texture_s3tc",
+            ],
+            // RGTC is core since GL 3.0, WebGL2 needs the extension
+            #[cfg(not(target_arch = "wasm32"))]
+            CompressedFormat::Bc4 { .. } | CompressedFormat::Bc5 { .. } => &[],
+            #[cfg(target_arch = "wasm32")]
+            CompressedFormat::Bc4 { .. } | CompressedFormat::Bc5 { .. } => {
+                &["EXT_texture_compression_rgtc"]
+            }
+            CompressedFormat::Bc6h { .. } | CompressedFormat::Bc7 => &[
+                "GL_ARB_texture_compression_bptc",
+                "GL_EXT_texture_compression_bptc",
+                "EXT_texture_compression_bptc",
+            ],
+            CompressedFormat::Etc2Rgb8
+            | CompressedFormat::Etc2Rgb8A1
+            | CompressedFormat::Etc2Rgba8
+            | CompressedFormat::EacR11 { .. }
+            | CompressedFormat::EacRg11 { .. } => {
+                &["GL_ARB_ES3_compatibility", "WEBGL_compressed_texture_etc"]
+            }
+            CompressedFormat::Astc { .. } => &[
+                "GL_KHR_texture_compression_astc_ldr",
+                "WEBGL_compressed_texture_astc",
+            ],
+        }
+    }
+
+    pub fn is_supported_by(&self, extensions: &HashSet<String>, srgb: bool) -> bool {
+        let required = self.required_extensions(srgb);
+        required.is_empty() || required.iter().any(|name| extensions.contains(*name))
+    }
+
+    // ETC2 / EAC are core in OpenGL ES 3.0, WebGL2 reports ES 3.0 but still needs the extension
+    pub fn is_core_in(&self, version: &glow::Version) -> bool {
+        let etc2 = matches!(
+            self,
+            CompressedFormat::Etc2Rgb8
+                | CompressedFormat::Etc2Rgb8A1
+                | CompressedFormat::Etc2Rgba8
+                | CompressedFormat::EacR11 { .. }
+                | CompressedFormat::EacRg11 { .. }
+        );
+        etc2 && version.is_embedded && version.major >= 3 && !cfg!(target_arch = "wasm32")
+    }
+
+    pub fn is_supported(&self, gl: &glow::Context, srgb: bool) -> bool {
+        self.is_core_in(gl.version()) || self.is_supported_by(gl.supported_extensions(), srgb)
+    }
+}
+
+// true for any internal format gl_internal_format can return, these textures only take
+// compressed uploads
+pub fn is_compressed_internal_format(internal_format: u32) -> bool {
+    matches!(
+        internal_format,
+        glow::COMPRESSED_RGB_S3TC_DXT1_EXT..=glow::COMPRESSED_RGBA_S3TC_DXT5_EXT
+            | glow::COMPRESSED_SRGB_S3TC_DXT1_EXT..=glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT
+            | glow::COMPRESSED_RED_RGTC1..=glow::COMPRESSED_SIGNED_RG_RGTC2
+            | glow::COMPRESSED_RGBA_BPTC_UNORM..=glow::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT
+            | glow::COMPRESSED_R11_EAC..=glow::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC
+            | glow::COMPRESSED_RGBA_ASTC_4x4_KHR..=glow::COMPRESSED_RGBA_ASTC_12x12_KHR
+            | glow::COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR..=glow::COMPRESSED_SRGB8_ALPHA8_ASTC_12x12_KHR
+    )
+}
+
+fn astc_internal_format(block_width: u32, block_height: u32, srgb: bool) -> Option<u32> {
+    let formats = match (block_width, block_height) {
+        (4, 4) => (
+            glow::COMPRESSED_RGBA_ASTC_4x4_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR,
+        ),
+        (5, 4) => (
+            glow::COMPRESSED_RGBA_ASTC_5x4_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_5x4_KHR,
+        ),
+        (5, 5) => (
+            glow::COMPRESSED_RGBA_ASTC_5x5_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_5x5_KHR,
+        ),
+        (6, 5) => (
+            glow::COMPRESSED_RGBA_ASTC_6x5_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_6x5_KHR,
+        ),
+        (6, 6) => (
+            glow::COMPRESSED_RGBA_ASTC_6x6_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_6x6_KHR,
+        ),
+        (8, 5) => (
+            glow::COMPRESSED_RGBA_ASTC_8x5_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_8x5_KHR,
+        ),
+        (8, 6) => (
+            glow::COMPRESSED_RGBA_ASTC_8x6_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_8x6_KHR,
+        ),
+        (8, 8) => (
+            glow::COMPRESSED_RGBA_ASTC_8x8_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_8x8_KHR,
+        ),
+        (10, 5) => (
+            glow::COMPRESSED_RGBA_ASTC_10x5_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_10x5_KHR,
+        ),
+        (10, 6) => (
+            glow::COMPRESSED_RGBA_ASTC_10x6_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_10x6_KHR,
+        ),
+        (10, 8) => (
+            glow::COMPRESSED_RGBA_ASTC_10x8_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_10x8_KHR,
+        ),
+        (10, 10) => (
+            glow::COMPRESSED_RGBA_ASTC_10x10_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_10x10_KHR,
+        ),
+        (12, 10) => (
+            glow::COMPRESSED_RGBA_ASTC_12x10_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_12x10_KHR,
+        ),
+        (12, 12) => (
+            glow::COMPRESSED_RGBA_ASTC_12x12_KHR,
+            glow::COMPRESSED_SRGB8_ALPHA8_ASTC_12x12_KHR,
+        ),
+        _ => return None,
+    };
+    Some(if srgb { formats.1 } else { formats.0 })
+}
+
+/// A parsed 2D container, `levels[0]` is the full size image followed by its mip levels.
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    pub format: CompressedFormat,
+    pub srgb: bool,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    pub fn level_dimensions(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+}
+
+impl Texture {
+    // KTX2 or DDS, the container is picked from the magic bytes
+    pub fn from_compressed_path<P: AsRef<Path>>(
+        gl: &glow::Context,
+        path: P,
+        settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        let bytes = std::fs::read(path).map_err(image::ImageError::IoError)?;
+        Self::from_compressed_bytes(gl, &bytes, settings)
+    }
+
+    pub fn from_compressed_bytes(
+        gl: &glow::Context,
+        bytes: &[u8],
+        settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        let image = texture_container::parse(bytes)?;
+        Self::from_compressed(gl, &image, settings)
+    }
+
+    // uploads every level in the container. Compressed textures can't generate mipmaps, so
+    // `settings.mipmaps` only picks the filter, the levels come from the file.
+    // Formats the context doesn't support are decoded on the CPU when possible ( BC1 - BC5 )
+    pub fn from_compressed(
+        gl: &glow::Context,
+        image: &CompressedImage,
+        mut settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        if settings.target != glow::TEXTURE_2D {
+            return Err(TextureError::UnsupportedFormat(
+                "compressed textures other than 2D".to_string(),
+            ));
+        }
+
+        let internal_format = image.format.gl_internal_format(image.srgb).ok_or_else(|| {
+            TextureError::UnsupportedFormat(format!("{:?} with srgb {}", image.format, image.srgb))
+        })?;
+
+        settings.mipmaps = false;
+        if image.format.is_supported(gl, image.srgb) {
+            settings.internal_format = internal_format;
+            settings.format = glow::RGBA;
+            settings.data_type = glow::UNSIGNED_BYTE;
+            return Ok(upload_compressed_levels(gl, image, settings));
+        }
+
+        let channels = bc_decoder::decoded_channels(image.format).ok_or_else(|| {
+            TextureError::UnsupportedFormat(format!(
+                "{:?} ( not supported by this context, and can't be decoded on the CPU )",
+                image.format
+            ))
+        })?;
+        println!(
+            "{:?} is not supported by this context, decoding on the CPU",
+            image.format
+        );
+
+        let (internal_format, format) = match channels {
+            1 => (glow::R8, glow::RED),
+            2 => (glow::RG8, glow::RG),
+            _ if image.srgb => (glow::SRGB8_ALPHA8, glow::RGBA),
+            _ => (glow::RGBA8, glow::RGBA),
+        };
+        settings.internal_format = internal_format;
+        settings.format = format;
+        settings.data_type = glow::UNSIGNED_BYTE;
+
+        let mut levels = Vec::with_capacity(image.levels.len());
+        for (level, data) in image.levels.iter().enumerate() {
+            let (width, height) = image.level_dimensions(level);
+            let pixels =
+                bc_decoder::decompress(image.format, width, height, data).ok_or_else(|| {
+                    TextureError::InvalidContainer(format!("level {} is truncated", level))
+                })?;
+            levels.push(pixels);
+        }
+        Ok(upload_levels(gl, image, &levels, settings))
+    }
+}
+
+fn upload_compressed_levels(
+    gl: &glow::Context,
+    image: &CompressedImage,
+    settings: TextureSettings,
+) -> Texture {
+    create_with_levels(gl, image, settings, |level, width, height| unsafe {
+        let data = &image.levels[level];
+        gl.compressed_tex_image_2d(
+            settings.target,
+            level as i32,
+            settings.internal_format as i32,
+            width,
+            height,
+            0,
+            data.len() as i32,
+            data,
+        );
+    })
+}
+
+fn upload_levels(
+    gl: &glow::Context,
+    image: &CompressedImage,
+    levels: &[Vec<u8>],
+    settings: TextureSettings,
+) -> Texture {
+    create_with_levels(gl, image, settings, |level, width, height| unsafe {
+        gl.tex_image_2d(
+            settings.target,
+            level as i32,
+            settings.internal_format as i32,
+            width,
+            height,
+            0,
+            settings.format,
+            settings.data_type,
+            Some(&levels[level]),
+        );
+    })
+}
+
+// the texture is complete with only the levels in the file
+fn create_with_levels<F: Fn(usize, i32, i32)>(
+    gl: &glow::Context,
+    image: &CompressedImage,
+    settings: TextureSettings,
+    upload_level: F,
+) -> Texture {
+    let texture_handle;
+    unsafe {
+        texture_handle = gl.create_texture().expect("Could not create texture");
+        gl.bind_texture(settings.target, Some(texture_handle));
+
+        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+        for level in 0..image.levels.len() {
+            let (width, height) = image.level_dimensions(level);
+            upload_level(level, width as i32, height as i32);
+        }
+        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+
+        gl.tex_parameter_i32(
+            settings.target,
+            glow::TEXTURE_MAX_LEVEL,
+            image.levels.len() as i32 - 1,
+        );
+        gl.bind_texture(settings.target, None);
+    }
+
+    let texture = Texture {
+        handle: Some(texture_handle),
+        width: image.width as i32,
+        height: image.height as i32,
+        depth: 1,
+        settings,
+    };
+    texture.apply_settings(gl);
+    texture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_sizes_round_up_to_blocks() {
+        let bc1 = CompressedFormat::Bc1 { alpha: false };
+        assert_eq!(bc1.level_size(256, 256), 64 * 64 * 8);
+        assert_eq!(bc1.level_size(1, 1), 8);
+        assert_eq!(CompressedFormat::Bc7.level_size(6, 6), 4 * 16);
+
+        let astc = CompressedFormat::Astc {
+            block_width: 6,
+            block_height: 5,
+        };
+        assert_eq!(astc.level_size(13, 10), 3 * 2 * 16);
+    }
+
+    #[test]
+    fn srgb_and_support() {
+        assert_eq!(
+            CompressedFormat::Bc7.gl_internal_format(true),
+            Some(glow::COMPRESSED_SRGB_ALPHA_BPTC_UNORM)
+        );
+        assert_eq!(
+            CompressedFormat::Bc4 { signed: false }.gl_internal_format(true),
+            None
+        );
+
+        let mut extensions = HashSet::new();
+        assert!(!CompressedFormat::Bc3.is_supported_by(&extensions, false));
+        extensions.insert("GL_EXT_texture_compression_s3tc".to_string());
+        assert!(CompressedFormat::Bc3.is_supported_by(&extensions, false));
+        assert!(!CompressedFormat::Bc3.is_supported_by(&extensions, true));
+    }
+
+    #[test]
+    fn compressed_internal_formats() {
+        let formats = [
+            CompressedFormat::Bc1 { alpha: false },
+            CompressedFormat::Bc3,
+            CompressedFormat::Bc5 { signed: true },
+            CompressedFormat::Bc6h { signed: false },
+            CompressedFormat::Etc2Rgba8,
+            CompressedFormat::Astc {
+                block_width: 12,
+                block_height: 12,
+            },
+        ];
+        for format in formats.iter() {
+            for srgb in [false, true] {
+                if let Some(internal_format) = format.gl_internal_format(srgb) {
+                    assert!(is_compressed_internal_format(internal_format));
+                }
+            }
+        }
+        assert!(!is_compressed_internal_format(glow::RGBA8));
+        assert!(!is_compressed_internal_format(glow::SRGB8_ALPHA8));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::gl_helper::shader_preprocessor;
+use crate::gl_helper::fbo::current_framebuffer;
+use crate::gl_helper::texture::{backend_compatible, upload_format, TextureSettings};
+use crate::gl_helper::texture_error::TextureError;
+use crate::gl_helper::{Bindable, GlslProg, Texture};
+use glow::{self, HasContext};
+use std::path::Path;
+
+// order of the faces in every cube map constructor, the same as the GL face targets
+pub const CUBE_FACES: [u32; 6] = [
+    glow::TEXTURE_CUBE_MAP_POSITIVE_X,
+    glow::TEXTURE_CUBE_MAP_NEGATIVE_X,
+    glow::TEXTURE_CUBE_MAP_POSITIVE_Y,
+    glow::TEXTURE_CUBE_MAP_NEGATIVE_Y,
+    glow::TEXTURE_CUBE_MAP_POSITIVE_Z,
+    glow::TEXTURE_CUBE_MAP_NEGATIVE_Z,
+];
+
+/// Where a face sits in a cross image, in face sized cells. `rotated` faces are upside down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrossCell {
+    pub column: u32,
+    pub row: u32,
+    pub rotated: bool,
+}
+
+impl TextureSettings {
+    // clamped, seams show between faces with any other wrap mode
+    pub fn cube_map() -> Self {
+        let mut settings = TextureSettings::default().wrap(glow::CLAMP_TO_EDGE);
+        settings.target = glow::TEXTURE_CUBE_MAP;
+        settings
+    }
+}
+
+impl Texture {
+    // six square faces of the same size and format, in +X, -X, +Y, -Y, +Z, -Z order.
+    // `data` of None allocates the faces, ie: to render in to them
+    pub fn new_cube_map_from_data(
+        gl: &glow::Context,
+        data: [Option<&[u8]>; 6],
+        size: i32,
+        mut settings: TextureSettings,
+    ) -> Self {
+        settings.target = glow::TEXTURE_CUBE_MAP;
+
+        let texture_handle;
+        unsafe {
+            texture_handle = gl.create_texture().expect("Could not create texture");
+            gl.bind_texture(settings.target, Some(texture_handle));
+
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            for (face, face_data) in CUBE_FACES.iter().zip(data.iter()) {
+                gl.tex_image_2d(
+                    *face,
+                    0,
+                    settings.internal_format as i32,
+                    size,
+                    size,
+                    0,
+                    settings.format,
+                    settings.data_type,
+                    *face_data,
+                );
+            }
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+
+            gl.bind_texture(settings.target, None);
+        }
+
+        let texture = Texture {
+            handle: Some(texture_handle),
+            width: size,
+            height: size,
+            depth: 1,
+            settings,
+        };
+        texture.apply_settings(gl);
+        if settings.mipmaps && data.iter().all(|face| face.is_some()) {
+            generate_mipmaps(gl, &texture);
+        }
+        texture
+    }
+
+    pub fn cube_map_from_images(
+        gl: &glow::Context,
+        faces: [&image::DynamicImage; 6],
+        mut settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        // 16 bit faces are narrowed on backends that can't sample them, as in from_image
+        let converted: Vec<Option<image::DynamicImage>> =
+            faces.iter().map(|face| backend_compatible(face)).collect();
+        let faces: Vec<&image::DynamicImage> = faces
+            .iter()
+            .zip(converted.iter())
+            .map(|(face, converted)| converted.as_ref().unwrap_or(face))
+            .collect();
+
+        let size = faces[0].width();
+        let format = upload_format(faces[0], settings.srgb)
+            .ok_or_else(|| TextureError::UnsupportedFormat(format!("{:?}", faces[0].color())))?;
+
+        for face in faces.iter() {
+            if face.width() != size || face.height() != size {
+                return Err(TextureError::InvalidDimensions(format!(
+                    "cube map faces must be square and of the same size, got {}x{} and {}x{}",
+                    size,
+                    size,
+                    face.width(),
+                    face.height()
+                )));
+            }
+            if upload_format(face, settings.srgb) != Some(format) {
+                return Err(TextureError::UnsupportedFormat(format!(
+                    "cube map faces of different formats, {:?} and {:?}",
+                    faces[0].color(),
+                    face.color()
+                )));
+            }
+        }
+
+        settings.internal_format = format.0;
+        settings.format = format.1;
+        settings.data_type = format.2;
+
+        let data = [
+            Some(faces[0].as_bytes()),
+            Some(faces[1].as_bytes()),
+            Some(faces[2].as_bytes()),
+            Some(faces[3].as_bytes()),
+            Some(faces[4].as_bytes()),
+            Some(faces[5].as_bytes()),
+        ];
+        Ok(Self::new_cube_map_from_data(
+            gl,
+            data,
+            size as i32,
+            settings,
+        ))
+    }
+
+    // six image files in +X, -X, +Y, -Y, +Z, -Z order
+    pub fn cube_map_from_paths<P: AsRef<Path>>(
+        gl: &glow::Context,
+        paths: [P; 6],
+        settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths.iter() {
+            faces.push(image::open(path)?);
+        }
+        Self::cube_map_from_images(
+            gl,
+            [
+                &faces[0], &faces[1], &faces[2], &faces[3], &faces[4], &faces[5],
+            ],
+            settings,
+        )
+    }
+
+    // a horizontal ( 4x3 ) or vertical ( 3x4 ) cross, the layout is picked from the aspect ratio
+    pub fn cube_map_from_cross(
+        gl: &glow::Context,
+        img: &image::DynamicImage,
+        settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        let (cells, size) = cross_layout(img.width(), img.height()).ok_or_else(|| {
+            TextureError::InvalidDimensions(format!(
+                "{}x{} is not a 4x3 or 3x4 cross",
+                img.width(),
+                img.height()
+            ))
+        })?;
+
+        let faces: Vec<image::DynamicImage> = cells
+            .iter()
+            .map(|cell| {
+                let face = img.crop_imm(cell.column * size, cell.row * size, size, size);
+                if cell.rotated {
+                    face.rotate180()
+                } else {
+                    face
+                }
+            })
+            .collect();
+
+        Self::cube_map_from_images(
+            gl,
+            [
+                &faces[0], &faces[1], &faces[2], &faces[3], &faces[4], &faces[5],
+            ],
+            settings,
+        )
+    }
+
+    // renders an equirectangular ( 2:1 latitude / longitude ) panorama in to the faces of a new
+    // cube map. float panoramas ( .hdr, .exr ) give a RGBA16F cube map, everything else RGBA8
+    pub fn cube_map_from_equirectangular(
+        gl: &glow::Context,
+        panorama: &Texture,
+        size: i32,
+        mut settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        let is_float = panorama.settings.data_type == glow::FLOAT
+            || panorama.settings.data_type == glow::HALF_FLOAT;
+        let is_srgb = matches!(
+            panorama.settings.internal_format,
+            glow::SRGB8 | glow::SRGB8_ALPHA8
+        );
+
+        let (internal_format, data_type) = if is_float {
+            (glow::RGBA16F, glow::HALF_FLOAT)
+        } else if is_srgb {
+            (glow::SRGB8_ALPHA8, glow::UNSIGNED_BYTE)
+        } else {
+            (glow::RGBA8, glow::UNSIGNED_BYTE)
+        };
+        settings.internal_format = internal_format;
+        settings.format = glow::RGBA;
+        settings.data_type = data_type;
+
+        let cube_map = Self::new_cube_map_from_data(gl, [None; 6], size, settings);
+
+        let prog = GlslProg::try_new(gl, &equirectangular_vertex(), &equirectangular_frag())?;
+
+        unsafe {
+            let mut viewport = [0; 4];
+            gl.get_parameter_i32_slice(glow::VIEWPORT, &mut viewport);
+            let previous_framebuffer = current_framebuffer(gl);
+            let depth_test = gl.is_enabled(glow::DEPTH_TEST);
+            let blend = gl.is_enabled(glow::BLEND);
+            gl.disable(glow::DEPTH_TEST);
+            gl.disable(glow::BLEND);
+
+            // ES encodes in to sRGB attachments always, desktop GL only when asked
+            #[cfg(not(target_arch = "wasm32"))]
+            if is_srgb {
+                gl.enable(glow::FRAMEBUFFER_SRGB);
+            }
+
+            let framebuffer = gl
+                .create_framebuffer()
+                .expect("could not create frame buffer");
+            let vertex_array = gl
+                .create_vertex_array()
+                .expect("could not create vertex array");
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            gl.bind_vertex_array(Some(vertex_array));
+            gl.viewport(0, 0, size, size);
+
+            prog.bind(gl);
+            prog.bind_texture(gl, "uPanorama", panorama);
+
+            for (index, face) in CUBE_FACES.iter().enumerate() {
+                gl.framebuffer_texture_2d(
+                    glow::FRAMEBUFFER,
+                    glow::COLOR_ATTACHMENT0,
+                    *face,
+                    cube_map.handle,
+                    0,
+                );
+                if gl.check_framebuffer_status(glow::FRAMEBUFFER) != glow::FRAMEBUFFER_COMPLETE {
+                    println!("Error creating framebuffer for cube map face {}", index);
+                }
+
+                prog.set(gl, "uFace", index as i32);
+                gl.draw_arrays(glow::TRIANGLES, 0, 3);
+            }
+
+            prog.unbind(gl);
+            gl.bind_vertex_array(None);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, previous_framebuffer);
+            gl.delete_vertex_array(vertex_array);
+            gl.delete_framebuffer(framebuffer);
+            prog.delete(gl);
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if is_srgb {
+                gl.disable(glow::FRAMEBUFFER_SRGB);
+            }
+            if depth_test {
+                gl.enable(glow::DEPTH_TEST);
+            }
+            if blend {
+                gl.enable(glow::BLEND);
+            }
+            gl.viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+
+        if settings.mipmaps {
+            generate_mipmaps(gl, &cube_map);
+        }
+        Ok(cube_map)
+    }
+
+    pub fn is_cube_map(&self) -> bool {
+        self.settings.target == glow::TEXTURE_CUBE_MAP
+    }
+}
+
+fn generate_mipmaps(gl: &glow::Context, texture: &Texture) {
+    texture.bind(gl);
+    unsafe { gl.generate_mipmap(texture.settings.target) };
+    texture.unbind(gl);
+}
+
+// faces in +X, -X, +Y, -Y, +Z, -Z order and the face size of a cross image
+pub fn cross_layout(width: u32, height: u32) -> Option<([CrossCell; 6], u32)> {
+    let cell = |column, row, rotated| CrossCell {
+        column,
+        row,
+        rotated,
+    };
+
+    //    +Y
+    // -X +Z +X -Z
+    //    -Y
+    if width * 3 == height * 4 && width % 4 == 0 {
+        let layout = [
+            cell(2, 1, false),
+            cell(0, 1, false),
+            cell(1, 0, false),
+            cell(1, 2, false),
+            cell(1, 1, false),
+            cell(3, 1, false),
+        ];
+        return Some((layout, width / 4));
+    }
+
+    //    +Y
+    // -X +Z +X
+    //    -Y
+    //    -Z  ( upside down )
+    if width * 4 == height * 3 && width % 3 == 0 {
+        let layout = [
+            cell(2, 1, false),
+            cell(0, 1, false),
+            cell(1, 0, false),
+            cell(1, 2, false),
+            cell(1, 1, false),
+            cell(1, 3, true),
+        ];
+        return Some((layout, width / 3));
+    }
+
+    None
+}
+
+// a triangle covering the viewport, without any vertex data
+fn equirectangular_vertex() -> String {
+    format!(
+        "{}
+    out vec2 vUv;
+    void main()
+    {{
+        vec2 position = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+        vUv = position * 2.0 - 1.0;
+        gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+    }}",
+        shader_preprocessor::default_header()
+    )
+}
+
+// direction of each face texel follows the cube map face table of the GL spec
+fn equirectangular_frag() -> String {
+    format!(
+        "{}
+    uniform sampler2D uPanorama;
+    uniform int uFace;
+    in vec2 vUv;
+
+    const float PI = 3.14159265359;
+
+    vec3 faceDirection(int face, vec2 uv)
+    {{
+        if (face == 0) return vec3(1.0, -uv.y, -uv.x);
+        if (face == 1) return vec3(-1.0, -uv.y, uv.x);
+        if (face == 2) return vec3(uv.x, 1.0, uv.y);
+        if (face == 3) return vec3(uv.x, -1.0, -uv.y);
+        if (face == 4) return vec3(uv.x, -uv.y, 1.0);
+        return vec3(-uv.x, -uv.y, -1.0);
+    }}
+
+    out vec4 Color;
+    void main()
+    {{
+        vec3 direction = normalize(faceDirection(uFace, vUv));
+        vec2 uv = vec2(atan(direction.z, direction.x) / (2.0 * PI) + 0.5, acos(clamp(direction.y, -1.0, 1.0)) / PI);
+        Color = vec4(textureLod(uPanorama, uv, 0.0).rgb, 1.0);
+    }}",
+        shader_preprocessor::default_header()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cross_layouts() {
+        let (horizontal, size) = cross_layout(1024, 768).unwrap();
+        assert_eq!(size, 256);
+        assert_eq!(
+            horizontal[4],
+            CrossCell {
+                column: 1,
+                row: 1,
+                rotated: false
+            }
+        );
+
+        let (vertical, size) = cross_layout(768, 1024).unwrap();
+        assert_eq!(size, 256);
+        assert!(vertical[5].rotated);
+        assert_eq!((vertical[5].column, vertical[5].row), (1, 3));
+
+        assert!(cross_layout(1024, 512).is_none());
+    }
+}
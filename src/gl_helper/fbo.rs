@@ -14,6 +14,7 @@ pub struct Fbo {
     pub fbo_handle: Option<glow::Framebuffer>,
     
     pub texture : Option<Texture>,
+    pub depth_texture : Option<Texture>,
     pub render_buffer : Option< glow::Renderbuffer >,
 
     settings: FboSettings,
@@ -34,6 +35,7 @@ impl Fbo {
             fbo_handle: Some(fbo),
             render_buffer : None,
             texture : None,
+            depth_texture : None,
             settings,
             texture_settings,
         };
@@ -88,6 +90,21 @@ impl Fbo {
         }
     }
 
+    // depth only fbos ( ie: shadow maps ) have no color buffer to draw to
+    pub fn attach_depth_texture(&mut self, gl : &glow::Context, texture : Texture){
+        if self.texture.is_none() {
+            unsafe {
+                gl.bind_framebuffer(glow::FRAMEBUFFER, self.fbo_handle);
+                gl.draw_buffers(&[glow::NONE]);
+                gl.read_buffer(glow::NONE);
+                gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            }
+        }
+
+        self.attach_texture(gl, &texture, glow::DEPTH_ATTACHMENT);
+        self.depth_texture = Some(texture);
+    }
+
     pub fn create_render_buffer(&mut self, gl : &glow::Context){
         unsafe {
 
@@ -131,3 +148,18 @@ impl Bindable for Fbo {
         }
     }
 }
+
+// the framebuffer bound right now, so passes that render off screen can put it back.
+// glow can't turn a GL name back in to a handle, the native handle is a NonZeroU32 newtype
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn current_framebuffer(gl: &glow::Context) -> Option<glow::Framebuffer> {
+    let name = unsafe { gl.get_parameter_i32(glow::FRAMEBUFFER_BINDING) } as u32;
+    std::num::NonZeroU32::new(name)
+        .map(|name| unsafe { std::mem::transmute::<std::num::NonZeroU32, glow::Framebuffer>(name) })
+}
+
+// WebGL handles live in glow's own tables, only the default framebuffer can be restored
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn current_framebuffer(_gl: &glow::Context) -> Option<glow::Framebuffer> {
+    None
+}
@@ -2,37 +2,172 @@
 extern  crate glam;
 use crate::gl_helper as glh;
 use crate::gl_helper::Bindable;
+use crate::gl_helper::shader_error::{ShaderCompileError, ShaderError, ShaderStage};
+use crate::gl_helper::program_reflection::ProgramReflection;
+use crate::gl_helper::uniform::{TextureBinding, Uniform};
+use crate::gl_helper::Texture;
 use glow::{self, HasContext};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-#[derive(Clone, Copy)]
+// clones share the reflection and the caches
+#[derive(Clone)]
 pub struct GlslProg {
     handle: Option<glow::Program>,
+    state: Rc<RefCell<ProgramState>>,
+    max_texture_units: u32,
+}
+
+#[derive(Default)]
+struct ProgramState {
+    reflection: Rc<ProgramReflection>,
+    uniform_locations: HashMap<String, Option<glow::UniformLocation>>,
+    texture_units: Vec<String>, // sampler names, indexed by the unit given to them since bind
+    #[cfg(debug_assertions)]
+    reported_mismatches: std::collections::HashSet<String>, // type warnings are printed once per name
 }
 
 impl GlslProg {
     pub fn new(gl: &glow::Context, vertex_source: &str, frag_source: &str) -> GlslProg {
-        let vertex_handle = compile_shader(gl, vertex_source, glow::VERTEX_SHADER);
-        let frag_handle = compile_shader(gl, frag_source, glow::FRAGMENT_SHADER);
+        Self::new_with_attrib_locations(gl, vertex_source, frag_source, &[])
+    }
+
+    pub fn new_with_attrib_locations(
+        gl: &glow::Context,
+        vertex_source: &str,
+        frag_source: &str,
+        attrib_locations: &[(&str, u32)],
+    ) -> GlslProg {
+        match Self::try_new_with_attrib_locations(gl, vertex_source, frag_source, attrib_locations)
+        {
+            Ok(prog) => prog,
+            Err(err) => {
+                println!("{}\n\n", err);
+                Self {
+                    handle: None,
+                    state: Rc::default(),
+                    max_texture_units: 0,
+                }
+            }
+        }
+    }
+
+    pub fn try_new(
+        gl: &glow::Context,
+        vertex_source: &str,
+        frag_source: &str,
+    ) -> Result<GlslProg, ShaderError> {
+        Self::try_new_with_attrib_locations(gl, vertex_source, frag_source, &[])
+    }
+
+    // binds the attributes to fixed locations before linking,
+    // the stock attribute names are always bound to their StockShader::attrib_location_*
+    pub fn try_new_with_attrib_locations(
+        gl: &glow::Context,
+        vertex_source: &str,
+        frag_source: &str,
+        attrib_locations: &[(&str, u32)],
+    ) -> Result<GlslProg, ShaderError> {
+        Self::link(
+            gl,
+            &[
+                (ShaderStage::Vertex, vertex_source),
+                (ShaderStage::Fragment, frag_source),
+            ],
+            attrib_locations,
+        )
+    }
+
+    // geometry and tessellation stages, see GlslProgBuilder
+    pub fn builder() -> GlslProgBuilder {
+        GlslProgBuilder::new()
+    }
+
+    fn link(
+        gl: &glow::Context,
+        stages: &[(ShaderStage, &str)],
+        attrib_locations: &[(&str, u32)],
+    ) -> Result<GlslProg, ShaderError> {
+        Self::link_prepared(gl, stages, attrib_locations, |_| {})
+    }
+
+    // `prepare` runs on the new program before anything is attached, e.g. for glProgramParameteri
+    pub(crate) fn link_prepared<F: FnOnce(glow::Program)>(
+        gl: &glow::Context,
+        stages: &[(ShaderStage, &str)],
+        attrib_locations: &[(&str, u32)],
+        prepare: F,
+    ) -> Result<GlslProg, ShaderError> {
+        let mut shader_handles = Vec::new();
+        for (stage, source) in stages {
+            match compile_shader(gl, source, stage.gl_type()) {
+                Ok(handle) => shader_handles.push(handle),
+                Err(err) => {
+                    for handle in shader_handles {
+                        unsafe { gl.delete_shader(handle) };
+                    }
+                    return Err(err.into());
+                }
+            }
+        }
 
         let program_id = unsafe { gl.create_program().unwrap() };
+        prepare(program_id);
 
         unsafe {
-            gl.attach_shader(program_id, vertex_handle);
-            gl.attach_shader(program_id, frag_handle);
+            for handle in &shader_handles {
+                gl.attach_shader(program_id, *handle);
+            }
+
+            for (name, loc) in glh::StockShader::attrib_locations() {
+                gl.bind_attrib_location(program_id, loc, name);
+            }
+            for (name, loc) in attrib_locations {
+                gl.bind_attrib_location(program_id, *loc, name);
+            }
+
             gl.link_program(program_id);
             let success = gl.get_program_link_status(program_id);
 
-            if !success {
-                println!("Could not LINK shader: {}\n\n", gl.get_program_info_log(program_id));
-                return Self { handle: None };
+            for handle in shader_handles {
+                gl.detach_shader(program_id, handle);
+                gl.delete_shader(handle);
             }
 
-            gl.detach_shader(program_id, vertex_handle);
-            gl.detach_shader(program_id, frag_handle);
+            if !success {
+                let log = gl.get_program_info_log(program_id);
+                gl.delete_program(program_id);
+                return Err(ShaderError::Link(log));
+            }
         }
 
+        Ok(Self::from_linked_program(gl, program_id))
+    }
+
+    // wraps a successfully linked program, every active uniform is looked up once here
+    // so the setters don't query the driver each frame
+    pub(crate) fn from_linked_program(gl: &glow::Context, program_id: glow::Program) -> GlslProg {
+        let reflection = ProgramReflection::new(gl, program_id);
+        let uniform_locations = reflection
+            .uniforms
+            .iter()
+            .filter_map(|u| Some((u.name.clone(), Some(u.location.as_ref().cloned()?))))
+            .collect();
+
+        let max_texture_units =
+            unsafe { gl.get_parameter_i32(glow::MAX_COMBINED_TEXTURE_IMAGE_UNITS) } as u32;
+
+        let state = ProgramState {
+            reflection: Rc::new(reflection),
+            uniform_locations,
+            ..ProgramState::default()
+        };
+
         GlslProg {
             handle: Some(program_id),
+            state: Rc::new(RefCell::new(state)),
+            max_texture_units,
         }
     }
 
@@ -40,12 +175,117 @@ impl GlslProg {
         self.handle
     }
 
+    pub fn get_attrib_location(&self, gl: &glow::Context, name: &str) -> Option<u32> {
+        unsafe { gl.get_attrib_location(self.handle?, name) }
+    }
+
+    pub fn get_reflection(&self) -> Rc<ProgramReflection> {
+        self.state.borrow().reflection.clone()
+    }
+
+    // cached, names that aren't in the reflection ( e.g. "lights[2].color" ) are queried once
+    pub fn try_get_uniform_location(
+        &self,
+        gl: &glow::Context,
+        name: &str,
+    ) -> Option<glow::UniformLocation> {
+        let handle = self.handle?;
+        self.state
+            .borrow_mut()
+            .uniform_locations
+            .entry(name.to_string())
+            .or_insert_with(|| unsafe { gl.get_uniform_location(handle, name) })
+            .as_ref()
+            .cloned()
+    }
+
     pub fn get_uniform_location(&self, gl: &glow::Context, name: &str) -> glow::UniformLocation {
-        let loc = unsafe {
-            gl.get_uniform_location(self.handle.unwrap(), name)
-                .expect(format!("\n\n\tno uniform named: {}\n\n", name).as_str())
+        self.try_get_uniform_location(gl, name)
+            .unwrap_or_else(|| panic!("\n\n\tno uniform named: {}\n\n", name))
+    }
+
+    // debug builds report, once per name, a uniform declared with a different type than the
+    // value written to it. The value is written either way
+    #[cfg(debug_assertions)]
+    fn check_uniform_type(&self, name: &str, expected_types: &[u32]) {
+        use crate::gl_helper::program_reflection::{is_sampler_type, uniform_type_name};
+
+        let mut state = self.state.borrow_mut();
+        let utype = {
+            let reflection = &state.reflection;
+            match reflection
+                .uniform(name)
+                .or_else(|| reflection.uniform(name.strip_suffix("[0]")?))
+            {
+                Some(info) => info.utype,
+                None => return, // struct members and such aren't in the reflection
+            }
         };
-        loc
+
+        let matches = expected_types.contains(&utype)
+            || (expected_types.contains(&glow::INT) && is_sampler_type(utype));
+        if !matches && state.reported_mismatches.insert(name.to_string()) {
+            println!(
+                "uniform {} is a {}, it is set as a {}",
+                name,
+                uniform_type_name(utype),
+                uniform_type_name(expected_types[0])
+            );
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn check_uniform_type(&self, _name: &str, _expected_types: &[u32]) {}
+
+    /// Writes any `Uniform` value ( scalars, glam vectors and matrices, `[T; N]` arrays, bools,
+    /// `TextureBinding`s ), panics when the program has no uniform called `name`.
+    pub fn set<U: Uniform>(&self, gl: &glow::Context, name: &str, value: U) {
+        let loc = self.get_uniform_location(gl, name);
+        self.check_uniform_type(name, U::GL_TYPES);
+        value.set_uniform(gl, &loc);
+    }
+
+    // for uniforms the compiler may have stripped out, returns false when nothing was written
+    pub fn try_set<U: Uniform>(&self, gl: &glow::Context, name: &str, value: U) -> bool {
+        match self.try_get_uniform_location(gl, name) {
+            Some(loc) => {
+                self.check_uniform_type(name, U::GL_TYPES);
+                value.set_uniform(gl, &loc);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Binds `texture` to a texture unit of its own and points the sampler `name` at it.
+    /// Units are handed out in call order and start again from 0 every time the program is bound,
+    /// binding the same sampler twice reuses its unit. Returns the unit.
+    pub fn bind_texture(&self, gl: &glow::Context, name: &str, texture: &Texture) -> u32 {
+        let unit = {
+            let units = &mut self.state.borrow_mut().texture_units;
+            match units.iter().position(|n| n == name) {
+                Some(unit) => unit,
+                None => {
+                    units.push(name.to_string());
+                    units.len() - 1
+                }
+            }
+        } as u32;
+
+        assert!(
+            unit < self.max_texture_units,
+            "\n\n\tout of texture units binding {}, only {} available\n\n",
+            name,
+            self.max_texture_units
+        );
+
+        self.set(gl, name, TextureBinding::new(texture, unit));
+        unit
+    }
+
+    // gives the units back, done by bind
+    pub fn reset_texture_units(&self) {
+        self.state.borrow_mut().texture_units.clear();
     }
 
     pub fn set_orthographic_matrix(&self, gl: &glow::Context, size: &[f32; 2]) {
@@ -94,56 +334,71 @@ impl GlslProg {
         self.set_uniform_4f(gl, glh::StockShader::uniform_name_color(), color);
     }
 
+    // lit StockShader uniforms ---
+    pub fn set_ambient_color(&self, gl: &glow::Context, color: glam::Vec3) {
+        self.set(gl, glh::StockShader::uniform_name_ambient_color(), color);
+    }
+
+    // phong only
+    pub fn set_specular(&self, gl: &glow::Context, color: glam::Vec3, shininess: f32) {
+        self.set(gl, glh::StockShader::uniform_name_specular_color(), color);
+        self.set(gl, glh::StockShader::uniform_name_shininess(), shininess);
+    }
+
+    // direction the light travels in world space, a zero color turns the light off
+    pub fn set_directional_light(
+        &self,
+        gl: &glow::Context,
+        index: usize,
+        direction: glam::Vec3,
+        color: glam::Vec3,
+    ) {
+        let lights = glh::StockShader::uniform_name_directional_lights();
+        self.set(gl, &format!("{}[{}].direction", lights, index), direction);
+        self.set(gl, &format!("{}[{}].color", lights, index), color);
+    }
+
+    pub fn set_point_light(
+        &self,
+        gl: &glow::Context,
+        index: usize,
+        position: glam::Vec3,
+        color: glam::Vec3,
+        range: f32,
+    ) {
+        let lights = glh::StockShader::uniform_name_point_lights();
+        self.set(gl, &format!("{}[{}].position", lights, index), position);
+        self.set(gl, &format!("{}[{}].color", lights, index), color);
+        self.set(gl, &format!("{}[{}].range", lights, index), range);
+    }
+
     
     pub fn set_uniform_mat4(&self, gl: &glow::Context, name: &str, value: &glam::Mat4) {
-        let mut slice : [f32; 16] = [0.0; 16];
-        value.write_cols_to_slice(&mut slice);
-        unsafe {
-            let loc = self.get_uniform_location(gl, name);
-            gl.uniform_matrix_4_f32_slice(Some(&loc), false, &slice);
-        };
+        self.set(gl, name, value);
     }
 
     pub fn set_uniform_mat4_slice(&self, gl: &glow::Context, name: &str, value: &[f32; 16]) {
-        unsafe {
-            let loc = self.get_uniform_location(gl, name);
-            gl.uniform_matrix_4_f32_slice(Some(&loc), false, value);
-        };
+        self.set(gl, name, glam::Mat4::from_cols_array(value));
     }
 
     pub fn set_uniform_1i(&self, gl: &glow::Context, name: &str, value: i32) {
-        unsafe {
-            let loc = self.get_uniform_location(gl, name);
-            gl.uniform_1_i32(Some(&loc), value);
-        };
+        self.set(gl, name, value);
     }
 
     pub fn set_uniform_1f(&self, gl: &glow::Context, name: &str, value: f32) {
-        unsafe {
-            let loc = self.get_uniform_location(gl, name);
-            gl.uniform_1_f32(Some(&loc), value);
-        };
+        self.set(gl, name, value);
     }
 
     pub fn set_uniform_2f(&self, gl: &glow::Context, name: &str, value: &[f32; 2]) {
-        unsafe {
-            let loc = self.get_uniform_location(gl, name);
-            gl.uniform_2_f32(Some(&loc), value[0], value[1]);
-        };
+        self.set(gl, name, glam::Vec2::from(*value));
     }
 
     pub fn set_uniform_3f(&self, gl: &glow::Context, name: &str, value: &[f32; 3]) {
-        unsafe {
-            let loc = self.get_uniform_location(gl, name);
-            gl.uniform_3_f32(Some(&loc), value[0], value[1], value[2]);
-        };
+        self.set(gl, name, glam::Vec3::from(*value));
     }
 
     pub fn set_uniform_4f(&self, gl: &glow::Context, name: &str, value: &[f32; 4]) {
-        unsafe {
-            let loc = self.get_uniform_location(gl, name);
-            gl.uniform_4_f32(Some(&loc), value[0], value[1], value[2], value[3]);
-        };
+        self.set(gl, name, glam::Vec4::from(*value));
     }
 
     pub fn bind(&self, gl: &glow::Context) {
@@ -151,6 +406,7 @@ impl GlslProg {
             assert!(self.handle != None);
             gl.use_program(self.handle);
         }
+        self.reset_texture_units();
     }
 
     pub fn unbind(&self, gl: &glow::Context) {
@@ -166,7 +422,101 @@ impl GlslProg {
     }
 }
 
-fn compile_shader(gl: &glow::Context, src: &str, shader_type: u32) -> glow::Shader {
+/// Collects the stages of a program, vertex and fragment are required. Stages the context
+/// can't run ( geometry before GL 3.2, tessellation before GL 4.0, both on WebGL2 ) fail the build
+/// with `ShaderError::UnsupportedStage` before anything is compiled.
+///
+/// ```ignore
+/// let prog = GlslProg::builder()
+///     .vertex(vertex_source)
+///     .geometry(wireframe_source)
+///     .fragment(frag_source)
+///     .build(gl)?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GlslProgBuilder {
+    stages: Vec<(ShaderStage, String)>,
+    attrib_locations: Vec<(String, u32)>,
+}
+
+impl GlslProgBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vertex(self, source: &str) -> Self {
+        self.stage(ShaderStage::Vertex, source)
+    }
+
+    pub fn fragment(self, source: &str) -> Self {
+        self.stage(ShaderStage::Fragment, source)
+    }
+
+    pub fn geometry(self, source: &str) -> Self {
+        self.stage(ShaderStage::Geometry, source)
+    }
+
+    pub fn tess_control(self, source: &str) -> Self {
+        self.stage(ShaderStage::TessControl, source)
+    }
+
+    pub fn tess_evaluation(self, source: &str) -> Self {
+        self.stage(ShaderStage::TessEvaluation, source)
+    }
+
+    // setting a stage twice replaces the previous source
+    pub fn stage(mut self, stage: ShaderStage, source: &str) -> Self {
+        self.stages.retain(|(s, _)| *s != stage);
+        self.stages.push((stage, source.to_string()));
+        self
+    }
+
+    pub fn attrib_location(mut self, name: &str, location: u32) -> Self {
+        self.attrib_locations.push((name.to_string(), location));
+        self
+    }
+
+    pub fn build(&self, gl: &glow::Context) -> Result<GlslProg, ShaderError> {
+        for required in &[ShaderStage::Vertex, ShaderStage::Fragment] {
+            if !self.stages.iter().any(|(stage, _)| stage == required) {
+                return Err(ShaderError::MissingStage(*required));
+            }
+        }
+        if let Some((stage, _)) = self.stages.iter().find(|(stage, _)| !stage.is_supported(gl)) {
+            return Err(ShaderError::UnsupportedStage(*stage));
+        }
+
+        // compile in pipeline order, so the first error reported is the earliest stage
+        let order = [
+            ShaderStage::Vertex,
+            ShaderStage::TessControl,
+            ShaderStage::TessEvaluation,
+            ShaderStage::Geometry,
+            ShaderStage::Fragment,
+            ShaderStage::Compute,
+        ];
+        let mut stages: Vec<(ShaderStage, &str)> = self
+            .stages
+            .iter()
+            .map(|(stage, source)| (*stage, source.as_str()))
+            .collect();
+        stages.sort_by_key(|(stage, _)| order.iter().position(|s| s == stage));
+
+        let attrib_locations: Vec<(&str, u32)> = self
+            .attrib_locations
+            .iter()
+            .map(|(name, loc)| (name.as_str(), *loc))
+            .collect();
+
+        GlslProg::link(gl, &stages, &attrib_locations)
+    }
+}
+
+fn compile_shader(
+    gl: &glow::Context,
+    src: &str,
+    shader_type: u32,
+) -> Result<glow::Shader, ShaderCompileError> {
     let shader_id = unsafe { gl.create_shader(shader_type).unwrap() };
 
     unsafe {
@@ -176,19 +526,13 @@ fn compile_shader(gl: &glow::Context, src: &str, shader_type: u32) -> glow::Shad
 
     let success = unsafe { gl.get_shader_compile_status(shader_id) };
     if !success {
-        let shader_type_string: &str;
-        match shader_type {
-            glow::VERTEX_SHADER => shader_type_string = "VERTEX_SHADER",
-            glow::FRAGMENT_SHADER => shader_type_string = "FRAGMENT",
-            glow::COMPUTE_SHADER => shader_type_string = "COMPUTE",
-            _ => shader_type_string = "unkwon shader type",
-        };
-        unsafe {
-            let log = gl.get_shader_info_log(shader_id);
-            println!("Failed to compile {} :: error {}", shader_type_string, log);
-        }
+        let stage = ShaderStage::from_gl_type(shader_type).expect("unknown shader type");
+        let log = unsafe { gl.get_shader_info_log(shader_id) };
+        unsafe { gl.delete_shader(shader_id) };
+
+        return Err(ShaderCompileError::new(stage, log, src));
     }
-    shader_id
+    Ok(shader_id)
 }
 
 impl Bindable for GlslProg {
@@ -200,6 +544,7 @@ impl Bindable for GlslProg {
             );
             gl.use_program(self.handle);
         }
+        self.reset_texture_units();
     }
 
     fn unbind(&self, gl: &glow::Context) {
@@ -4,20 +4,102 @@ pub use self::vbo::Vbo;
 pub mod vao;
 pub use self::vao::Vao;
 pub use self::vao::VertexAttrib;
+pub use self::vao::AttribType;
+pub use self::vao::DrawRange;
+
+pub mod vertex_layout;
+pub use self::vertex_layout::LayoutAttrib;
+pub use self::vertex_layout::VertexLayout;
+
+pub mod vertex;
+pub use self::vertex::Vertex;
+pub use self::vertex::VertexComponent;
+pub use pira_derive::Vertex;
+
+pub mod vertex_data;
+pub use self::vertex_data::VertexData;
 
 pub mod stock_shader;
+pub use self::stock_shader::Lighting;
 pub use self::stock_shader::StockShader;
 
+pub mod pbr_material;
+pub use self::pbr_material::PbrEnvironment;
+pub use self::pbr_material::PbrMaps;
+pub use self::pbr_material::PbrMaterial;
+
 pub mod glsl_prog;
 pub use self::glsl_prog::GlslProg;
+pub use self::glsl_prog::GlslProgBuilder;
+
+pub mod shader_error;
+pub use self::shader_error::ShaderCompileError;
+pub use self::shader_error::ShaderError;
+pub use self::shader_error::ShaderStage;
+
+pub mod shader_preprocessor;
+pub use self::shader_preprocessor::PreprocessError;
+pub use self::shader_preprocessor::PreprocessedShader;
+pub use self::shader_preprocessor::ShaderPreprocessor;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod program_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::program_cache::ProgramCache;
+
+pub mod program_reflection;
+pub use self::program_reflection::AttributeInfo;
+pub use self::program_reflection::ProgramReflection;
+pub use self::program_reflection::UniformBlockInfo;
+pub use self::program_reflection::UniformInfo;
+
+pub mod uniform;
+pub use self::uniform::TextureBinding;
+pub use self::uniform::Uniform;
+pub use self::uniform::UniformArrayElement;
 
 pub mod texture;
 pub use self::texture::Texture;
+pub use self::texture::TextureSettings;
+
+pub mod cube_map;
+pub use self::cube_map::CrossCell;
+
+pub mod async_texture;
+pub use self::async_texture::AsyncTexture;
+
+pub mod texture_readback;
+pub use self::texture_readback::ReadbackLayout;
+
+pub mod compressed_texture;
+pub use self::compressed_texture::CompressedFormat;
+pub use self::compressed_texture::CompressedImage;
+
+pub mod texture_container;
+pub mod bc_decoder;
+
+pub mod texture_atlas;
+pub use self::texture_atlas::AtlasBuilder;
+pub use self::texture_atlas::AtlasError;
+pub use self::texture_atlas::AtlasLayout;
+pub use self::texture_atlas::AtlasRegion;
+pub use self::texture_atlas::PackingAlgorithm;
+pub use self::texture_atlas::TextureAtlas;
+pub use self::texture_atlas::UvRect;
+
+pub mod texture_error;
+pub use self::texture_error::TextureError;
 
 pub mod fbo;
 pub use self::fbo::Fbo;
 pub use self::fbo::FboSettings;
 
+pub mod shadow_map;
+pub use self::shadow_map::ShadowCaster;
+pub use self::shadow_map::ShadowLight;
+pub use self::shadow_map::ShadowMap;
+pub use self::shadow_map::ShadowSettings;
+
 use glow::*;
 
 pub trait Bindable {
@@ -0,0 +1,186 @@
+use crate::gl_helper::{GlslProg, StockShader, Texture};
+
+/// Texture maps sampled by `StockShader::pbr`, must match the maps set on the `PbrMaterial`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PbrMaps {
+    pub base_color: bool,
+    pub metallic_roughness: bool,
+    pub normal: bool,
+    pub occlusion: bool,
+    pub emissive: bool,
+}
+
+impl PbrMaps {
+    pub fn any(&self) -> bool {
+        self.base_color || self.metallic_roughness || self.normal || self.occlusion || self.emissive
+    }
+}
+
+/// glTF 2.0 metallic-roughness material for the pbr stock shader.
+/// Color textures ( base color, emissive ) should use an sRGB internal format so they are
+/// sampled in linear space, the other maps are linear data.
+///
+/// ```ignore
+/// let material = glh::PbrMaterial {
+///     base_color_map: Some(albedo),
+///     roughness: 0.5,
+///     ..Default::default()
+/// };
+/// let shader = glh::StockShader::new().pbr(material.maps()).build(gl);
+/// shader.bind(gl);
+/// material.apply(gl, &shader);
+/// ```
+#[derive(Clone, Copy)]
+pub struct PbrMaterial {
+    pub base_color_factor: glam::Vec4,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive_factor: glam::Vec3,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+
+    pub base_color_map: Option<Texture>,
+    pub metallic_roughness_map: Option<Texture>, // roughness in green, metallic in blue
+    pub normal_map: Option<Texture>,
+    pub occlusion_map: Option<Texture>, // occlusion in red
+    pub emissive_map: Option<Texture>,
+}
+
+impl Default for PbrMaterial {
+    // the glTF defaults
+    fn default() -> Self {
+        Self {
+            base_color_factor: glam::Vec4::ONE,
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive_factor: glam::Vec3::ZERO,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+
+            base_color_map: None,
+            metallic_roughness_map: None,
+            normal_map: None,
+            occlusion_map: None,
+            emissive_map: None,
+        }
+    }
+}
+
+impl PbrMaterial {
+    pub fn maps(&self) -> PbrMaps {
+        PbrMaps {
+            base_color: self.base_color_map.is_some(),
+            metallic_roughness: self.metallic_roughness_map.is_some(),
+            normal: self.normal_map.is_some(),
+            occlusion: self.occlusion_map.is_some(),
+            emissive: self.emissive_map.is_some(),
+        }
+    }
+
+    // sets the factors and binds the maps, the program has to be bound
+    pub fn apply(&self, gl: &glow::Context, prog: &GlslProg) {
+        self.apply_factors(gl, prog);
+
+        let maps = [
+            (
+                StockShader::uniform_name_base_color_map(),
+                &self.base_color_map,
+            ),
+            (
+                StockShader::uniform_name_metallic_roughness_map(),
+                &self.metallic_roughness_map,
+            ),
+            (StockShader::uniform_name_normal_map(), &self.normal_map),
+            (
+                StockShader::uniform_name_occlusion_map(),
+                &self.occlusion_map,
+            ),
+            (StockShader::uniform_name_emissive_map(), &self.emissive_map),
+        ];
+        for (name, map) in maps.iter() {
+            if let Some(texture) = map {
+                prog.bind_texture(gl, name, texture);
+            }
+        }
+    }
+
+    // uniforms of maps the shader wasn't built with don't exist, so they are set with try_set
+    pub(crate) fn apply_factors(&self, gl: &glow::Context, prog: &GlslProg) {
+        prog.try_set(
+            gl,
+            StockShader::uniform_name_color(),
+            self.base_color_factor,
+        );
+        prog.try_set(
+            gl,
+            StockShader::uniform_name_metallic_factor(),
+            self.metallic,
+        );
+        prog.try_set(
+            gl,
+            StockShader::uniform_name_roughness_factor(),
+            self.roughness,
+        );
+        prog.try_set(
+            gl,
+            StockShader::uniform_name_emissive_factor(),
+            self.emissive_factor,
+        );
+        prog.try_set(
+            gl,
+            StockShader::uniform_name_normal_scale(),
+            self.normal_scale,
+        );
+        prog.try_set(
+            gl,
+            StockShader::uniform_name_occlusion_strength(),
+            self.occlusion_strength,
+        );
+    }
+}
+
+/// Image based lighting for `StockShader::image_based_lighting`.
+/// `irradiance` is the diffuse convolved cube map, `prefiltered` the specular cube map with
+/// increasing roughness per mip level, `mip_levels` is the index of its roughest level.
+#[derive(Clone, Copy)]
+pub struct PbrEnvironment {
+    pub irradiance: Texture,
+    pub prefiltered: Texture,
+    pub mip_levels: f32,
+    pub intensity: f32,
+}
+
+impl PbrEnvironment {
+    pub fn new(irradiance: Texture, prefiltered: Texture, mip_levels: f32) -> Self {
+        Self {
+            irradiance,
+            prefiltered,
+            mip_levels,
+            intensity: 1.0,
+        }
+    }
+
+    // the program has to be bound
+    pub fn apply(&self, gl: &glow::Context, prog: &GlslProg) {
+        prog.bind_texture(
+            gl,
+            StockShader::uniform_name_irradiance_map(),
+            &self.irradiance,
+        );
+        prog.bind_texture(
+            gl,
+            StockShader::uniform_name_prefiltered_map(),
+            &self.prefiltered,
+        );
+        prog.set(
+            gl,
+            StockShader::uniform_name_prefiltered_mip_levels(),
+            self.mip_levels,
+        );
+        prog.set(
+            gl,
+            StockShader::uniform_name_environment_intensity(),
+            self.intensity,
+        );
+    }
+}
@@ -0,0 +1,420 @@
+use crate::gl_helper::glsl_prog::GlslProg;
+use crate::gl_helper::shader_error::{ShaderError, ShaderStage};
+use crate::gl_helper::shader_preprocessor;
+use crate::gl_helper::StockShader;
+use glow::{self, HasContext};
+use std::ffi::c_void;
+use std::path::PathBuf;
+
+const PROGRAM_BINARY_RETRIEVABLE_HINT: u32 = 0x8257;
+const PROGRAM_BINARY_LENGTH: u32 = 0x8741;
+const NUM_PROGRAM_BINARY_FORMATS: u32 = 0x87FE;
+
+// file layout: magic, binary format ( u32 little endian ), binary
+const MAGIC: &[u8; 8] = b"PIRAPRG1";
+
+type GetProgramivFn = unsafe extern "system" fn(u32, u32, *mut i32);
+type GetProgramBinaryFn = unsafe extern "system" fn(u32, i32, *mut i32, *mut u32, *mut c_void);
+type ProgramBinaryFn = unsafe extern "system" fn(u32, u32, *const c_void, i32);
+type ProgramParameteriFn = unsafe extern "system" fn(u32, u32, i32);
+type UseProgramFn = unsafe extern "system" fn(u32);
+
+// glow doesn't wrap glGetProgramBinary / glProgramBinary, so they are loaded here
+struct ProgramBinaryFns {
+    get_programiv: GetProgramivFn,
+    get_program_binary: GetProgramBinaryFn,
+    program_binary: ProgramBinaryFn,
+    program_parameteri: ProgramParameteriFn,
+    use_program: UseProgramFn, // restores a program glow has no handle for
+    // trivial shaders a new program is linked against to read back its GL name
+    probe_shaders: [glow::Shader; 2],
+}
+
+/// Opt-in disk cache of linked program binaries, keyed by a hash of the sources and the
+/// vendor / renderer / version strings of the driver. Binaries the driver rejects ( e.g. after
+/// a driver update that kept the version string ) are deleted and rebuilt from source.
+///
+/// ```ignore
+/// let cache = glh::ProgramCache::new(gl, "shader_cache", |s| app.context.get_proc_address(s));
+/// let shader = glh::StockShader::new().color().try_build_cached(gl, &cache)?;
+/// ```
+pub struct ProgramCache {
+    dir: PathBuf,
+    driver: String,
+    fns: Option<ProgramBinaryFns>,
+}
+
+impl ProgramCache {
+    pub fn new<F>(gl: &glow::Context, dir: impl Into<PathBuf>, mut loader: F) -> Self
+    where
+        F: FnMut(&str) -> *const c_void,
+    {
+        let version = gl.version();
+        let supported = (!version.is_embedded && (version.major, version.minor) >= (4, 1))
+            || (version.is_embedded && version.major >= 3)
+            || gl
+                .supported_extensions()
+                .contains("GL_ARB_get_program_binary");
+
+        let fns = if supported && unsafe { gl.get_parameter_i32(NUM_PROGRAM_BINARY_FORMATS) } > 0 {
+            load_fns(gl, &mut loader)
+        } else {
+            None
+        };
+
+        let driver = unsafe {
+            format!(
+                "{}|{}|{}",
+                gl.get_parameter_string(glow::VENDOR),
+                gl.get_parameter_string(glow::RENDERER),
+                gl.get_parameter_string(glow::VERSION)
+            )
+        };
+
+        Self {
+            dir: dir.into(),
+            driver,
+            fns,
+        }
+    }
+
+    // false when the driver has no program binary formats, every build then compiles from source
+    pub fn is_supported(&self) -> bool {
+        self.fns.is_some()
+    }
+
+    pub fn delete(&self, gl: &glow::Context) {
+        if let Some(fns) = &self.fns {
+            for shader in &fns.probe_shaders {
+                unsafe { gl.delete_shader(*shader) };
+            }
+        }
+    }
+
+    pub fn build(
+        &self,
+        gl: &glow::Context,
+        vertex_source: &str,
+        frag_source: &str,
+    ) -> Result<GlslProg, ShaderError> {
+        self.build_with_attrib_locations(gl, vertex_source, frag_source, &[])
+    }
+
+    pub fn build_with_attrib_locations(
+        &self,
+        gl: &glow::Context,
+        vertex_source: &str,
+        frag_source: &str,
+        attrib_locations: &[(&str, u32)],
+    ) -> Result<GlslProg, ShaderError> {
+        let fns = match &self.fns {
+            Some(fns) => fns,
+            None => {
+                return GlslProg::try_new_with_attrib_locations(
+                    gl,
+                    vertex_source,
+                    frag_source,
+                    attrib_locations,
+                )
+            }
+        };
+
+        let path = self.path(vertex_source, frag_source, attrib_locations);
+
+        if let Some(prog) = load(gl, fns, &path) {
+            return Ok(prog);
+        }
+
+        // some drivers only keep the binary around when asked to before linking
+        let mut name = None;
+        let prog = GlslProg::link_prepared(
+            gl,
+            &[
+                (ShaderStage::Vertex, vertex_source),
+                (ShaderStage::Fragment, frag_source),
+            ],
+            attrib_locations,
+            |program| unsafe {
+                name = program_name(gl, fns, program);
+                if let Some(name) = name {
+                    (fns.program_parameteri)(
+                        name,
+                        PROGRAM_BINARY_RETRIEVABLE_HINT,
+                        glow::TRUE as i32,
+                    );
+                }
+            },
+        )?;
+        if let Some(name) = name {
+            if let Err(err) = store(fns, name, &path) {
+                println!("could not write program cache {}: {}", path.display(), err);
+            }
+        }
+        Ok(prog)
+    }
+
+    pub fn clear(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    fn path(
+        &self,
+        vertex_source: &str,
+        frag_source: &str,
+        attrib_locations: &[(&str, u32)],
+    ) -> PathBuf {
+        let mut hash = Fnv1a::new();
+        hash.write(self.driver.as_bytes());
+        hash.write(vertex_source.as_bytes());
+        hash.write(frag_source.as_bytes());
+
+        // locations are baked in to the binary
+        for (name, loc) in StockShader::attrib_locations()
+            .iter()
+            .chain(attrib_locations.iter())
+        {
+            hash.write(name.as_bytes());
+            hash.write(&loc.to_le_bytes());
+        }
+
+        self.dir.join(format!("{:016x}.bin", hash.finish()))
+    }
+}
+
+fn load(gl: &glow::Context, fns: &ProgramBinaryFns, path: &PathBuf) -> Option<GlslProg> {
+    let data = std::fs::read(path).ok()?;
+    let (format, binary) = match parse_cache_file(&data) {
+        Some(parsed) => parsed,
+        None => {
+            let _ = std::fs::remove_file(path);
+            return None;
+        }
+    };
+
+    unsafe {
+        let program = gl.create_program().ok()?;
+        let name = match program_name(gl, fns, program) {
+            Some(name) => name,
+            None => {
+                gl.delete_program(program);
+                return None;
+            }
+        };
+        (fns.program_binary)(
+            name,
+            format,
+            binary.as_ptr() as *const c_void,
+            binary.len() as i32,
+        );
+
+        if gl.get_program_link_status(program) {
+            Some(GlslProg::from_linked_program(gl, program))
+        } else {
+            // stale or rejected by the driver, rebuild from source
+            gl.delete_program(program);
+            let _ = std::fs::remove_file(path);
+            None
+        }
+    }
+}
+
+fn store(fns: &ProgramBinaryFns, name: u32, path: &PathBuf) -> std::io::Result<()> {
+    let mut length = 0;
+    unsafe { (fns.get_programiv)(name, PROGRAM_BINARY_LENGTH, &mut length) };
+    if length <= 0 {
+        return Ok(());
+    }
+
+    let mut binary = vec![0u8; length as usize];
+    let mut written = 0;
+    let mut format = 0;
+    unsafe {
+        (fns.get_program_binary)(
+            name,
+            length,
+            &mut written,
+            &mut format,
+            binary.as_mut_ptr() as *mut c_void,
+        )
+    };
+    binary.truncate(written.max(0) as usize);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, cache_file(format, &binary))
+}
+
+// MAGIC, the binary format as a little endian u32, then the binary
+fn cache_file(format: u32, binary: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(MAGIC.len() + 4 + binary.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&format.to_le_bytes());
+    data.extend_from_slice(binary);
+    data
+}
+
+// the binary format and the binary, None for files that aren't a cached program
+fn parse_cache_file(data: &[u8]) -> Option<(u32, &[u8])> {
+    if data.len() <= MAGIC.len() + 4 || &data[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+
+    let mut format = [0u8; 4];
+    format.copy_from_slice(&data[MAGIC.len()..MAGIC.len() + 4]);
+    Some((u32::from_le_bytes(format), &data[MAGIC.len() + 4..]))
+}
+
+fn load_fns<F>(gl: &glow::Context, loader: &mut F) -> Option<ProgramBinaryFns>
+where
+    F: FnMut(&str) -> *const c_void,
+{
+    let mut load = |name: &str| {
+        let ptr = loader(name);
+        if ptr.is_null() {
+            None
+        } else {
+            Some(ptr)
+        }
+    };
+
+    // every pointer is checked before it is turned in to a function
+    let get_programiv = load("glGetProgramiv")?;
+    let get_program_binary = load("glGetProgramBinary")?;
+    let program_binary = load("glProgramBinary")?;
+    let program_parameteri = load("glProgramParameteri")?;
+    let use_program = load("glUseProgram")?;
+
+    let probe_shaders = compile_probe_shaders(gl)?;
+
+    unsafe {
+        Some(ProgramBinaryFns {
+            get_programiv: std::mem::transmute::<*const c_void, GetProgramivFn>(get_programiv),
+            get_program_binary: std::mem::transmute::<*const c_void, GetProgramBinaryFn>(
+                get_program_binary,
+            ),
+            program_binary: std::mem::transmute::<*const c_void, ProgramBinaryFn>(program_binary),
+            program_parameteri: std::mem::transmute::<*const c_void, ProgramParameteriFn>(
+                program_parameteri,
+            ),
+            use_program: std::mem::transmute::<*const c_void, UseProgramFn>(use_program),
+            probe_shaders,
+        })
+    }
+}
+
+fn compile_probe_shaders(gl: &glow::Context) -> Option<[glow::Shader; 2]> {
+    let header = shader_preprocessor::default_header();
+    let vertex = format!("{}\nvoid main() {{ gl_Position = vec4(0.0); }}\n", header);
+    let fragment = format!(
+        "{}\nout vec4 fragColor;\nvoid main() {{ fragColor = vec4(0.0); }}\n",
+        header
+    );
+
+    let mut shaders = Vec::new();
+    for (shader_type, source) in [
+        (glow::VERTEX_SHADER, vertex),
+        (glow::FRAGMENT_SHADER, fragment),
+    ] {
+        unsafe {
+            let shader = gl.create_shader(shader_type).ok()?;
+            gl.shader_source(shader, &source);
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                gl.delete_shader(shader);
+                for shader in shaders {
+                    gl.delete_shader(shader);
+                }
+                return None;
+            }
+            shaders.push(shader);
+        }
+    }
+    Some([shaders[0], shaders[1]])
+}
+
+// glow 0.11 keeps the GL name of a program private, it is read back from CURRENT_PROGRAM.
+// glUseProgram needs a linked program, so a new one is first linked against the probe
+// shaders, glProgramBinary or the real link replace that executable afterwards
+unsafe fn program_name(
+    gl: &glow::Context,
+    fns: &ProgramBinaryFns,
+    program: glow::Program,
+) -> Option<u32> {
+    for shader in &fns.probe_shaders {
+        gl.attach_shader(program, *shader);
+    }
+    gl.link_program(program);
+    for shader in &fns.probe_shaders {
+        gl.detach_shader(program, *shader);
+    }
+    if !gl.get_program_link_status(program) {
+        return None;
+    }
+
+    let previous = gl.get_parameter_i32(glow::CURRENT_PROGRAM);
+    gl.use_program(Some(program));
+    let name = gl.get_parameter_i32(glow::CURRENT_PROGRAM) as u32;
+    (fns.use_program)(previous as u32);
+    Some(name)
+}
+
+// stable across runs and platforms, unlike std's DefaultHasher
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+        // separator, so ( "ab", "c" ) and ( "a", "bc" ) hash differently
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(chunks: &[&str]) -> u64 {
+        let mut hasher = Fnv1a::new();
+        for chunk in chunks {
+            hasher.write(chunk.as_bytes());
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn hash_separates_chunks() {
+        assert_ne!(hash(&["ab", "c"]), hash(&["a", "bc"]));
+        assert_ne!(hash(&["a", ""]), hash(&["a"]));
+        assert_eq!(hash(&["a", "bc"]), hash(&["a", "bc"]));
+    }
+
+    #[test]
+    fn cache_file_round_trip() {
+        let data = cache_file(0x8741, &[1, 2, 3]);
+        assert_eq!(parse_cache_file(&data), Some((0x8741, &[1u8, 2, 3][..])));
+
+        // truncated before or right after the format
+        assert_eq!(parse_cache_file(&data[..6]), None);
+        assert_eq!(parse_cache_file(&data[..MAGIC.len() + 4]), None);
+
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(parse_cache_file(&bad_magic), None);
+    }
+}
@@ -0,0 +1,211 @@
+use glow::{self, HasContext};
+
+#[derive(Debug, Clone)]
+pub struct UniformInfo {
+    pub name: String, // array uniforms are reported without the [0] suffix
+    pub utype: u32,
+    pub array_size: i32,
+    pub location: Option<glow::UniformLocation>, // None for uniforms inside a uniform block
+}
+
+#[derive(Debug, Clone)]
+pub struct AttributeInfo {
+    pub name: String,
+    pub atype: u32,
+    pub array_size: i32,
+    pub location: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UniformBlockInfo {
+    pub name: String,
+    pub index: u32,
+    pub data_size: i32,
+    pub binding: i32,
+    pub active_uniforms: i32,
+}
+
+/// Active uniforms, attributes and uniform blocks of a linked program, as reported by the driver.
+#[derive(Debug, Clone, Default)]
+pub struct ProgramReflection {
+    pub uniforms: Vec<UniformInfo>,
+    pub attributes: Vec<AttributeInfo>,
+    pub uniform_blocks: Vec<UniformBlockInfo>,
+}
+
+impl ProgramReflection {
+    pub fn new(gl: &glow::Context, program: glow::Program) -> Self {
+        let uniforms = reflect_uniforms(gl, program);
+        let uniform_blocks = reflect_uniform_blocks(gl, program, &uniforms);
+        Self {
+            uniforms,
+            attributes: reflect_attributes(gl, program),
+            uniform_blocks,
+        }
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&UniformInfo> {
+        self.uniforms.iter().find(|u| u.name == name)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&AttributeInfo> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    pub fn uniform_block(&self, name: &str) -> Option<&UniformBlockInfo> {
+        self.uniform_blocks.iter().find(|b| b.name == name)
+    }
+}
+
+fn strip_array_suffix(name: String) -> String {
+    match name.strip_suffix("[0]") {
+        Some(base) => base.to_string(),
+        None => name,
+    }
+}
+
+fn reflect_uniforms(gl: &glow::Context, program: glow::Program) -> Vec<UniformInfo> {
+    let count = unsafe { gl.get_active_uniforms(program) };
+
+    (0..count)
+        .filter_map(|index| unsafe { gl.get_active_uniform(program, index) })
+        .map(|uniform| {
+            let location = unsafe { gl.get_uniform_location(program, &uniform.name) };
+            UniformInfo {
+                name: strip_array_suffix(uniform.name),
+                utype: uniform.utype,
+                array_size: uniform.size,
+                location,
+            }
+        })
+        .collect()
+}
+
+fn reflect_attributes(gl: &glow::Context, program: glow::Program) -> Vec<AttributeInfo> {
+    let count = unsafe { gl.get_active_attributes(program) };
+
+    (0..count)
+        .filter_map(|index| unsafe { gl.get_active_attribute(program, index) })
+        .map(|attribute| {
+            let location = unsafe { gl.get_attrib_location(program, &attribute.name) };
+            AttributeInfo {
+                name: strip_array_suffix(attribute.name),
+                atype: attribute.atype,
+                array_size: attribute.size,
+                location,
+            }
+        })
+        .collect()
+}
+
+// glow has no way to query GL_ACTIVE_UNIFORM_BLOCKS. Every active block has at least one
+// active member and members are the only uniforms without a location ( atomic counters aside ),
+// so the indices are walked until all members are accounted for, never past the last block
+fn reflect_uniform_blocks(
+    gl: &glow::Context,
+    program: glow::Program,
+    uniforms: &[UniformInfo],
+) -> Vec<UniformBlockInfo> {
+    let members = uniforms
+        .iter()
+        .filter(|u| u.location.is_none() && u.utype != glow::UNSIGNED_INT_ATOMIC_COUNTER)
+        .count() as i32;
+    let max_blocks =
+        unsafe { gl.get_parameter_i32(glow::MAX_COMBINED_UNIFORM_BLOCKS) }.max(0) as u32;
+
+    let mut blocks = Vec::new();
+    let mut found_members = 0;
+
+    unsafe {
+        for index in 0..max_blocks {
+            if found_members >= members {
+                break;
+            }
+
+            let data_size = gl.get_active_uniform_block_parameter_i32(
+                program,
+                index,
+                glow::UNIFORM_BLOCK_DATA_SIZE,
+            );
+            let active_uniforms = gl.get_active_uniform_block_parameter_i32(
+                program,
+                index,
+                glow::UNIFORM_BLOCK_ACTIVE_UNIFORMS,
+            );
+            if data_size <= 0 || active_uniforms <= 0 {
+                break;
+            }
+            found_members += active_uniforms;
+
+            blocks.push(UniformBlockInfo {
+                name: gl.get_active_uniform_block_name(program, index),
+                index,
+                data_size,
+                binding: gl.get_active_uniform_block_parameter_i32(
+                    program,
+                    index,
+                    glow::UNIFORM_BLOCK_BINDING,
+                ),
+                active_uniforms,
+            });
+        }
+    }
+
+    blocks
+}
+
+pub fn uniform_type_name(utype: u32) -> &'static str {
+    match utype {
+        glow::FLOAT => "float",
+        glow::FLOAT_VEC2 => "vec2",
+        glow::FLOAT_VEC3 => "vec3",
+        glow::FLOAT_VEC4 => "vec4",
+        glow::INT => "int",
+        glow::INT_VEC2 => "ivec2",
+        glow::INT_VEC3 => "ivec3",
+        glow::INT_VEC4 => "ivec4",
+        glow::UNSIGNED_INT => "uint",
+        glow::UNSIGNED_INT_VEC2 => "uvec2",
+        glow::UNSIGNED_INT_VEC3 => "uvec3",
+        glow::UNSIGNED_INT_VEC4 => "uvec4",
+        glow::BOOL => "bool",
+        glow::BOOL_VEC2 => "bvec2",
+        glow::BOOL_VEC3 => "bvec3",
+        glow::BOOL_VEC4 => "bvec4",
+        glow::FLOAT_MAT2 => "mat2",
+        glow::FLOAT_MAT3 => "mat3",
+        glow::FLOAT_MAT4 => "mat4",
+        glow::SAMPLER_2D => "sampler2D",
+        glow::SAMPLER_3D => "sampler3D",
+        glow::SAMPLER_CUBE => "samplerCube",
+        glow::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        glow::SAMPLER_2D_ARRAY => "sampler2DArray",
+        glow::SAMPLER_2D_ARRAY_SHADOW => "sampler2DArrayShadow",
+        glow::INT_SAMPLER_2D => "isampler2D",
+        glow::UNSIGNED_INT_SAMPLER_2D => "usampler2D",
+        _ => "unknown",
+    }
+}
+
+pub const SAMPLER_TYPES: &[u32] = &[
+    glow::SAMPLER_2D,
+    glow::SAMPLER_3D,
+    glow::SAMPLER_CUBE,
+    glow::SAMPLER_2D_SHADOW,
+    glow::SAMPLER_2D_ARRAY,
+    glow::SAMPLER_2D_ARRAY_SHADOW,
+    glow::SAMPLER_CUBE_SHADOW,
+    glow::INT_SAMPLER_2D,
+    glow::INT_SAMPLER_3D,
+    glow::INT_SAMPLER_CUBE,
+    glow::INT_SAMPLER_2D_ARRAY,
+    glow::UNSIGNED_INT_SAMPLER_2D,
+    glow::UNSIGNED_INT_SAMPLER_3D,
+    glow::UNSIGNED_INT_SAMPLER_CUBE,
+    glow::UNSIGNED_INT_SAMPLER_2D_ARRAY,
+];
+
+// types that are set with glUniform1i
+pub fn is_sampler_type(utype: u32) -> bool {
+    SAMPLER_TYPES.contains(&utype)
+}
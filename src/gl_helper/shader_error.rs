@@ -0,0 +1,317 @@
+use crate::gl_helper::shader_preprocessor::PreprocessError;
+use glow::HasContext;
+use std::fmt;
+
+// lines of source shown before and after each error
+const CONTEXT_LINES: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Geometry,
+    TessControl,
+    TessEvaluation,
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn gl_type(&self) -> u32 {
+        match self {
+            ShaderStage::Vertex => glow::VERTEX_SHADER,
+            ShaderStage::Fragment => glow::FRAGMENT_SHADER,
+            ShaderStage::Geometry => glow::GEOMETRY_SHADER,
+            ShaderStage::TessControl => glow::TESS_CONTROL_SHADER,
+            ShaderStage::TessEvaluation => glow::TESS_EVALUATION_SHADER,
+            ShaderStage::Compute => glow::COMPUTE_SHADER,
+        }
+    }
+
+    pub fn from_gl_type(shader_type: u32) -> Option<Self> {
+        match shader_type {
+            glow::VERTEX_SHADER => Some(ShaderStage::Vertex),
+            glow::FRAGMENT_SHADER => Some(ShaderStage::Fragment),
+            glow::GEOMETRY_SHADER => Some(ShaderStage::Geometry),
+            glow::TESS_CONTROL_SHADER => Some(ShaderStage::TessControl),
+            glow::TESS_EVALUATION_SHADER => Some(ShaderStage::TessEvaluation),
+            glow::COMPUTE_SHADER => Some(ShaderStage::Compute),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShaderStage::Vertex => "VERTEX",
+            ShaderStage::Fragment => "FRAGMENT",
+            ShaderStage::Geometry => "GEOMETRY",
+            ShaderStage::TessControl => "TESS_CONTROL",
+            ShaderStage::TessEvaluation => "TESS_EVALUATION",
+            ShaderStage::Compute => "COMPUTE",
+        }
+    }
+
+    // geometry needs GL 3.2, tessellation and compute GL 4.0 / 4.3, ES 3.2 has them all and WebGL2 none
+    pub fn is_supported(&self, gl: &glow::Context) -> bool {
+        let version = gl.version();
+        let at_least = |major, minor| (version.major, version.minor) >= (major, minor);
+
+        match self {
+            ShaderStage::Vertex | ShaderStage::Fragment => true,
+            _ if cfg!(target_arch = "wasm32") => false,
+            _ if version.is_embedded => at_least(3, 2),
+            ShaderStage::Geometry => at_least(3, 2),
+            ShaderStage::TessControl | ShaderStage::TessEvaluation => at_least(4, 0),
+            ShaderStage::Compute => at_least(4, 3),
+        }
+    }
+}
+
+/// One message of the driver info log, `line` is 1 based and `None` when the driver didn't give one.
+/// `file` is set once the line has been mapped back to a preprocessor input, otherwise the line
+/// refers to the compiled source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderLogEntry {
+    pub line: Option<u32>,
+    pub file: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShaderCompileError {
+    pub stage: ShaderStage,
+    pub log: String,
+    pub entries: Vec<ShaderLogEntry>,
+    pub source: String,
+    pub files: Vec<(String, String)>, // ( name, source ) of the files the entries point at
+}
+
+impl ShaderCompileError {
+    pub fn new(stage: ShaderStage, log: String, source: &str) -> Self {
+        Self {
+            stage,
+            entries: parse_info_log(&log),
+            log,
+            source: source.to_string(),
+            files: Vec::new(),
+        }
+    }
+
+    fn source_of(&self, entry: &ShaderLogEntry) -> &str {
+        match &entry.file {
+            Some(file) => self
+                .files
+                .iter()
+                .find(|(name, _)| name == file)
+                .map(|(_, source)| source.as_str())
+                .unwrap_or(""),
+            None => &self.source,
+        }
+    }
+}
+
+impl fmt::Display for ShaderCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Failed to compile {} shader", self.stage.name())?;
+
+        for entry in &self.entries {
+            let source_lines: Vec<&str> = self.source_of(entry).lines().collect();
+
+            match entry.line {
+                Some(line) => {
+                    match &entry.file {
+                        Some(file) => writeln!(f, "\n{}:{}: {}", file, line, entry.message)?,
+                        None => writeln!(f, "\nline {}: {}", line, entry.message)?,
+                    }
+
+                    let line_index = line as usize;
+                    let first = line_index.saturating_sub(CONTEXT_LINES).max(1);
+                    let last = (line_index + CONTEXT_LINES).min(source_lines.len());
+                    for n in first..=last {
+                        let marker = if n == line_index { ">" } else { " " };
+                        writeln!(f, "{} {:4} | {}", marker, n, source_lines[n - 1])?;
+                    }
+                }
+                None => writeln!(f, "\n{}", entry.message)?,
+            }
+        }
+
+        // nothing we could parse, show the raw log instead
+        if self.entries.is_empty() {
+            writeln!(f, "{}", self.log)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ShaderError {
+    Compile(ShaderCompileError),
+    Link(String),
+    Preprocess(PreprocessError),
+    UnsupportedStage(ShaderStage),
+    MissingStage(ShaderStage),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Compile(err) => write!(f, "{}", err),
+            ShaderError::Link(log) => write!(f, "Could not LINK shader: {}", log),
+            ShaderError::Preprocess(err) => write!(f, "{}", err),
+            ShaderError::UnsupportedStage(stage) => {
+                write!(
+                    f,
+                    "{} shaders are not supported by this context",
+                    stage.name()
+                )
+            }
+            ShaderError::MissingStage(stage) => write!(f, "a {} shader is required", stage.name()),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl From<ShaderCompileError> for ShaderError {
+    fn from(err: ShaderCompileError) -> Self {
+        ShaderError::Compile(err)
+    }
+}
+
+impl From<PreprocessError> for ShaderError {
+    fn from(err: PreprocessError) -> Self {
+        ShaderError::Preprocess(err)
+    }
+}
+
+/// Splits a driver info log in ( line, message ) pairs. Understands the formats used by
+///  NVIDIA:        `0(12) : error C0000: message`
+///  Mesa:          `0:12(5): error: message`
+///  ANGLE / others: `ERROR: 0:12: message`
+pub fn parse_info_log(log: &str) -> Vec<ShaderLogEntry> {
+    log.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            parse_nvidia_line(line)
+                .or_else(|| parse_mesa_line(line))
+                .or_else(|| parse_angle_line(line))
+                .unwrap_or_else(|| ShaderLogEntry {
+                    line: None,
+                    file: None,
+                    message: line.to_string(),
+                })
+        })
+        .collect()
+}
+
+// splits "123rest" in ( 123, "rest" )
+fn take_number(text: &str) -> Option<(u32, &str)> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    if end == 0 {
+        return None;
+    }
+    Some((text[..end].parse().ok()?, &text[end..]))
+}
+
+fn parse_nvidia_line(line: &str) -> Option<ShaderLogEntry> {
+    let (_, rest) = take_number(line)?;
+    let rest = rest.strip_prefix('(')?;
+    let (line_number, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix(')')?.trim_start().strip_prefix(':')?;
+
+    Some(ShaderLogEntry {
+        line: Some(line_number),
+        file: None,
+        message: rest.trim().to_string(),
+    })
+}
+
+fn parse_mesa_line(line: &str) -> Option<ShaderLogEntry> {
+    let (_, rest) = take_number(line)?;
+    let rest = rest.strip_prefix(':')?;
+    let (line_number, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix('(')?;
+    let (_column, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix(')')?.strip_prefix(':')?;
+
+    Some(ShaderLogEntry {
+        line: Some(line_number),
+        file: None,
+        message: rest.trim().to_string(),
+    })
+}
+
+fn parse_angle_line(line: &str) -> Option<ShaderLogEntry> {
+    let (severity, rest) = line.split_once(':')?;
+    let severity = severity.trim();
+    if severity != "ERROR" && severity != "WARNING" {
+        return None;
+    }
+
+    let (_, rest) = take_number(rest.trim_start())?;
+    let rest = rest.strip_prefix(':')?;
+    let (line_number, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix(':')?;
+
+    Some(ShaderLogEntry {
+        line: Some(line_number),
+        file: None,
+        message: format!("{}: {}", severity.to_lowercase(), rest.trim()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nvidia_log() {
+        let entries = parse_info_log("0(12) : error C0000: syntax error, unexpected '}'\n");
+        assert_eq!(
+            entries,
+            vec![ShaderLogEntry {
+                line: Some(12),
+                file: None,
+                message: "error C0000: syntax error, unexpected '}'".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_mesa_log() {
+        let entries = parse_info_log("0:7(14): error: `foo' undeclared\n0:9(2): warning: unused\n");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].line, Some(7));
+        assert_eq!(entries[0].message, "error: `foo' undeclared");
+        assert_eq!(entries[1].line, Some(9));
+    }
+
+    #[test]
+    fn parses_angle_log() {
+        let log = "ERROR: 0:3: 'vColour' : undeclared identifier\nERROR: 1 compilation errors.  No code generated.\n";
+        let entries = parse_info_log(log);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].line, Some(3));
+        assert_eq!(
+            entries[0].message,
+            "error: 'vColour' : undeclared identifier"
+        );
+        assert_eq!(entries[1].line, None);
+    }
+
+    #[test]
+    fn shows_source_context() {
+        let source = "#version 300 es\nvoid main()\n{\n    foo();\n}\n";
+        let err = ShaderCompileError::new(
+            ShaderStage::Fragment,
+            "ERROR: 0:4: 'foo' : no matching overloaded function found".to_string(),
+            source,
+        );
+        let text = err.to_string();
+        assert!(text.contains(">    4 |     foo();"));
+        assert!(text.contains("     2 | void main()"));
+    }
+}
@@ -0,0 +1,503 @@
+use crate::gl_helper::glsl_prog::GlslProg;
+use crate::gl_helper::shader_error::{ShaderCompileError, ShaderError, ShaderStage};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_HEADER: &str = "#version 400";
+
+#[cfg(target_arch = "wasm32")]
+const DEFAULT_HEADER: &str = "#version 300 es\nprecision highp float;\nprecision highp int;";
+
+/// `#version` directive of the active backend, desktop GL 4.0 or WebGL2.
+pub fn version_directive() -> &'static str {
+    DEFAULT_HEADER.lines().next().unwrap()
+}
+
+/// Version directive plus the default precision ES needs, prepended to sources without a `#version`.
+pub fn default_header() -> &'static str {
+    DEFAULT_HEADER
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32, // 1 based
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PreprocessError {
+    IncludeNotFound {
+        file: String,
+        from: Option<SourceLocation>,
+    },
+    IncludeCycle {
+        file: String,
+        from: SourceLocation,
+    },
+    MalformedInclude(SourceLocation),
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PreprocessError::IncludeNotFound {
+                file,
+                from: Some(from),
+            } => write!(f, "{}: can't find included file \"{}\"", from, file),
+            PreprocessError::IncludeNotFound { file, from: None } => {
+                write!(f, "can't find shader file \"{}\"", file)
+            }
+            PreprocessError::IncludeCycle { file, from } => {
+                write!(f, "{}: \"{}\" includes itself", from, file)
+            }
+            PreprocessError::MalformedInclude(from) => {
+                write!(f, "{}: expected #include \"file\" or #include <file>", from)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// Output of the preprocessor, `line_map[n]` is where line `n + 1` of `source` came from,
+/// `None` for the lines the preprocessor generated ( header and defines ).
+#[derive(Debug, Clone)]
+pub struct PreprocessedShader {
+    pub source: String,
+    pub line_map: Vec<Option<SourceLocation>>,
+    pub files: Vec<(String, String)>,
+}
+
+impl PreprocessedShader {
+    pub fn original_location(&self, line: u32) -> Option<&SourceLocation> {
+        let index = (line as usize).checked_sub(1)?;
+        self.line_map.get(index)?.as_ref()
+    }
+
+    // points the driver log entries at the files they came from
+    pub fn remap_error(&self, mut err: ShaderCompileError) -> ShaderCompileError {
+        for entry in &mut err.entries {
+            if let Some(location) = entry.line.and_then(|line| self.original_location(line)) {
+                entry.line = Some(location.line);
+                entry.file = Some(location.file.clone());
+            }
+        }
+        err.files = self.files.clone();
+        err
+    }
+}
+
+/// Expands `#include "file"`, honours `#pragma once`, injects `#define`s and prepends the
+/// version header of the active backend, so the same GLSL builds on desktop and WebGL2.
+///
+/// ```ignore
+/// let prog = ShaderPreprocessor::new()
+///     .add_file("common/noise.glsl", NOISE_SOURCE)
+///     .include_dir("assets/shaders")
+///     .define("NUM_LIGHTS", 4)
+///     .build(gl, vertex_source, frag_source)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShaderPreprocessor {
+    files: HashMap<String, String>,
+    include_dirs: Vec<PathBuf>,
+    defines: Vec<(String, String)>,
+    header: Option<String>,
+}
+
+impl Default for ShaderPreprocessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// state of one process call
+struct Expansion {
+    lines: Vec<(String, Option<SourceLocation>)>,
+    once: HashSet<String>,
+    stack: Vec<String>,
+    files: Vec<(String, String)>,
+}
+
+impl ShaderPreprocessor {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+            include_dirs: Vec::new(),
+            defines: Vec::new(),
+            header: Some(default_header().to_string()),
+        }
+    }
+
+    // virtual file, looked up before the include directories
+    pub fn add_file(mut self, name: &str, source: &str) -> Self {
+        self.files.insert(normalize_path(name), source.to_string());
+        self
+    }
+
+    pub fn include_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.include_dirs.push(dir.into());
+        self
+    }
+
+    pub fn define<V: fmt::Display>(mut self, name: &str, value: V) -> Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // replaces the backend header, used when a source has no #version of its own
+    pub fn header(mut self, header: &str) -> Self {
+        self.header = Some(header.to_string());
+        self
+    }
+
+    pub fn no_header(mut self) -> Self {
+        self.header = None;
+        self
+    }
+
+    pub fn process(&self, name: &str, source: &str) -> Result<PreprocessedShader, PreprocessError> {
+        let mut expansion = Expansion {
+            lines: Vec::new(),
+            once: HashSet::new(),
+            stack: Vec::new(),
+            files: Vec::new(),
+        };
+        self.expand(&normalize_path(name), source, &mut expansion)?;
+
+        let mut body = expansion.lines;
+        let mut lines = Vec::new();
+
+        // #version has to be the first line, defines go right after it
+        let version = body
+            .iter()
+            .position(|(line, _)| line.trim_start().starts_with("#version"));
+        match (version, &self.header) {
+            (Some(index), _) => lines.push(body.remove(index)),
+            (None, Some(header)) => {
+                lines.extend(header.lines().map(|line| (line.to_string(), None)))
+            }
+            (None, None) => {}
+        }
+        for (name, value) in &self.defines {
+            lines.push((
+                format!("#define {} {}", name, value).trim_end().to_string(),
+                None,
+            ));
+        }
+        lines.extend(body);
+
+        let mut source = String::new();
+        for (line, _) in &lines {
+            source.push_str(line);
+            source.push('\n');
+        }
+
+        Ok(PreprocessedShader {
+            source,
+            line_map: lines.into_iter().map(|(_, location)| location).collect(),
+            files: expansion.files,
+        })
+    }
+
+    // reads the shader from the virtual files or the include directories
+    pub fn process_file(&self, name: &str) -> Result<PreprocessedShader, PreprocessError> {
+        let (name, source) =
+            self.resolve(name, "")
+                .ok_or_else(|| PreprocessError::IncludeNotFound {
+                    file: name.to_string(),
+                    from: None,
+                })?;
+        self.process(&name, &source)
+    }
+
+    pub fn build(
+        &self,
+        gl: &glow::Context,
+        vertex_source: &str,
+        frag_source: &str,
+    ) -> Result<GlslProg, ShaderError> {
+        let vertex = self.process("vertex", vertex_source)?;
+        let frag = self.process("fragment", frag_source)?;
+        build_processed(gl, &vertex, &frag)
+    }
+
+    pub fn build_files(
+        &self,
+        gl: &glow::Context,
+        vertex_file: &str,
+        frag_file: &str,
+    ) -> Result<GlslProg, ShaderError> {
+        let vertex = self.process_file(vertex_file)?;
+        let frag = self.process_file(frag_file)?;
+        build_processed(gl, &vertex, &frag)
+    }
+
+    fn expand(
+        &self,
+        name: &str,
+        source: &str,
+        expansion: &mut Expansion,
+    ) -> Result<(), PreprocessError> {
+        expansion.stack.push(name.to_string());
+        if !expansion.files.iter().any(|(file, _)| file == name) {
+            expansion.files.push((name.to_string(), source.to_string()));
+        }
+
+        for (index, line) in source.lines().enumerate() {
+            let location = SourceLocation {
+                file: name.to_string(),
+                line: index as u32 + 1,
+            };
+            let directive = line.trim();
+
+            if directive.starts_with("#pragma")
+                && directive.split_whitespace().nth(1) == Some("once")
+            {
+                expansion.once.insert(name.to_string());
+                continue;
+            }
+
+            if let Some(rest) = directive.strip_prefix("#include") {
+                let include = parse_include(rest)
+                    .ok_or_else(|| PreprocessError::MalformedInclude(location.clone()))?;
+                let (include_name, include_source) =
+                    self.resolve(include, name).ok_or_else(|| {
+                        PreprocessError::IncludeNotFound {
+                            file: include.to_string(),
+                            from: Some(location.clone()),
+                        }
+                    })?;
+
+                if expansion.once.contains(&include_name) {
+                    continue;
+                }
+                if expansion.stack.contains(&include_name) {
+                    return Err(PreprocessError::IncludeCycle {
+                        file: include_name,
+                        from: location,
+                    });
+                }
+                self.expand(&include_name, &include_source, expansion)?;
+                continue;
+            }
+
+            expansion.lines.push((line.to_string(), Some(location)));
+        }
+
+        expansion.stack.pop();
+        Ok(())
+    }
+
+    // relative to the including file first, then as given; virtual files before the disk
+    fn resolve(&self, include: &str, from_file: &str) -> Option<(String, String)> {
+        let mut candidates = Vec::new();
+        if let Some(dir) = from_file.rfind('/').map(|end| &from_file[..end]) {
+            candidates.push(normalize_path(&format!("{}/{}", dir, include)));
+        }
+        candidates.push(normalize_path(include));
+
+        for candidate in &candidates {
+            if let Some(source) = self.files.get(candidate) {
+                return Some((candidate.clone(), source.clone()));
+            }
+        }
+
+        for candidate in &candidates {
+            for dir in &self.include_dirs {
+                if let Ok(source) = std::fs::read_to_string(dir.join(candidate)) {
+                    return Some((candidate.clone(), source));
+                }
+            }
+        }
+        None
+    }
+}
+
+fn build_processed(
+    gl: &glow::Context,
+    vertex: &PreprocessedShader,
+    frag: &PreprocessedShader,
+) -> Result<GlslProg, ShaderError> {
+    GlslProg::try_new(gl, &vertex.source, &frag.source).map_err(|err| match err {
+        ShaderError::Compile(err) if err.stage == ShaderStage::Vertex => {
+            ShaderError::Compile(vertex.remap_error(err))
+        }
+        ShaderError::Compile(err) => ShaderError::Compile(frag.remap_error(err)),
+        err => err,
+    })
+}
+
+// "file" or <file>
+fn parse_include(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    let name = rest
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .or_else(|| {
+            rest.strip_prefix('<')
+                .and_then(|rest| rest.strip_suffix('>'))
+        })?;
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+// forward slashes, no "." or ".." components, so the same file always gets the same name
+fn normalize_path(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." if components.last().is_some_and(|last| *last != "..") => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(file: &str, line: u32) -> Option<SourceLocation> {
+        Some(SourceLocation {
+            file: file.to_string(),
+            line,
+        })
+    }
+
+    #[test]
+    fn expands_includes_and_maps_lines() {
+        let preprocessor = ShaderPreprocessor::new()
+            .header("#version 330")
+            .add_file(
+                "lib/common.glsl",
+                "float one() { return 1.0; }\n#include \"math.glsl\"",
+            )
+            .add_file("lib/math.glsl", "#pragma once\nfloat two() { return 2.0; }");
+
+        let shader = preprocessor
+            .process("main.glsl", "#include \"lib/common.glsl\"\nvoid main() {}")
+            .unwrap();
+
+        assert_eq!(
+            shader.source,
+            "#version 330\nfloat one() { return 1.0; }\nfloat two() { return 2.0; }\nvoid main() {}\n"
+        );
+        assert_eq!(
+            shader.line_map,
+            vec![
+                None,
+                location("lib/common.glsl", 1),
+                location("lib/math.glsl", 2),
+                location("main.glsl", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn pragma_once_includes_a_file_once() {
+        let preprocessor = ShaderPreprocessor::new()
+            .no_header()
+            .add_file("a.glsl", "#pragma once\nfloat a;");
+
+        let shader = preprocessor
+            .process(
+                "main.glsl",
+                "#include \"a.glsl\"\n#include <a.glsl>\nvoid main() {}",
+            )
+            .unwrap();
+        assert_eq!(shader.source, "float a;\nvoid main() {}\n");
+    }
+
+    #[test]
+    fn keeps_version_first_and_injects_defines() {
+        let preprocessor = ShaderPreprocessor::new()
+            .define("NUM_LIGHTS", 4)
+            .define("USE_FOG", "");
+
+        let shader = preprocessor
+            .process("main.glsl", "// comment\n#version 300 es\nvoid main() {}")
+            .unwrap();
+
+        assert_eq!(
+            shader.source,
+            "#version 300 es\n#define NUM_LIGHTS 4\n#define USE_FOG\n// comment\nvoid main() {}\n"
+        );
+        assert_eq!(
+            shader.original_location(1),
+            location("main.glsl", 2).as_ref()
+        );
+        assert_eq!(shader.original_location(2), None);
+        assert_eq!(
+            shader.original_location(4),
+            location("main.glsl", 1).as_ref()
+        );
+    }
+
+    #[test]
+    fn reports_missing_and_recursive_includes() {
+        let missing = ShaderPreprocessor::new().process("main.glsl", "\n#include \"nope.glsl\"");
+        match missing {
+            Err(PreprocessError::IncludeNotFound { file, from }) => {
+                assert_eq!(file, "nope.glsl");
+                assert_eq!(from, location("main.glsl", 2));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let recursive = ShaderPreprocessor::new()
+            .add_file("a.glsl", "#include \"b.glsl\"")
+            .add_file("b.glsl", "#include \"a.glsl\"")
+            .process_file("a.glsl");
+        assert!(matches!(
+            recursive,
+            Err(PreprocessError::IncludeCycle { .. })
+        ));
+    }
+
+    #[test]
+    fn remaps_compile_errors_to_original_files() {
+        let preprocessor = ShaderPreprocessor::new()
+            .header("#version 330")
+            .add_file("noise.glsl", "float noise() {\n    return bad;\n}");
+        let shader = preprocessor
+            .process("fragment", "#include \"noise.glsl\"\nvoid main() {}")
+            .unwrap();
+
+        let err = ShaderCompileError::new(
+            ShaderStage::Fragment,
+            "0:3(12): error: `bad' undeclared".to_string(),
+            &shader.source,
+        );
+        let err = shader.remap_error(err);
+
+        assert_eq!(err.entries[0].line, Some(2));
+        assert_eq!(err.entries[0].file.as_deref(), Some("noise.glsl"));
+        assert!(err.to_string().contains("noise.glsl:2: error"));
+        assert!(err.to_string().contains(">    2 |     return bad;"));
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(
+            normalize_path("./lib/../common\\light.glsl"),
+            "common/light.glsl"
+        );
+        assert_eq!(normalize_path("../shared.glsl"), "../shared.glsl");
+    }
+}
@@ -0,0 +1,570 @@
+use crate::gl_helper::fbo::current_framebuffer;
+use crate::gl_helper::texture::TextureSettings;
+use crate::gl_helper::{Bindable, Fbo, FboSettings, GlslProg, StockShader, Texture, Vao};
+use glow::{self, HasContext};
+
+// the directional map fits at most this many cascades
+pub const MAX_SHADOW_CASCADES: u32 = 4;
+
+/// The light a `ShadowMap` renders depth from.
+/// A directional map shadows directional light 0 of the receiving shader, a spot map shadows
+/// point light 0, which should sit at the spot position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadowLight {
+    Directional {
+        direction: glam::Vec3, // direction the light travels
+    },
+    Spot {
+        position: glam::Vec3,
+        direction: glam::Vec3,
+        angle: f32, // full cone angle in radians
+        range: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    pub resolution: i32,    // width and height of each cascade, at most MAX_TEXTURE_SIZE
+    pub cascades: u32,      // directional only, spot maps always have one
+    pub max_distance: f32,  // directional shadows end here, or at the camera far plane
+    pub split_lambda: f32,  // 0 splits the cascades evenly, 1 logarithmically
+    pub caster_margin: f32, // casters this far behind a cascade still cast in to it
+
+    pub bias: f32,        // depth bias in the [0, 1] range of the map
+    pub normal_bias: f32, // world units the receiver is pushed along its normal
+    pub pcf_radius: i32,  // (2r + 1)^2 taps, 0 for a single hardware filtered tap
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 2048,
+            cascades: 3,
+            max_distance: 100.0,
+            split_lambda: 0.75,
+            caster_margin: 50.0,
+            bias: 0.0005,
+            normal_bias: 0.02,
+            pcf_radius: 1,
+        }
+    }
+}
+
+/// A draw call of the shadow pass. Vaos with instances need a matching `shader`,
+/// ie: `StockShader::new().instanced_transform().build(gl)`.
+pub struct ShadowCaster<'a> {
+    pub vao: &'a Vao,
+    pub model_matrix: glam::Mat4,
+    pub shader: Option<&'a GlslProg>,
+}
+
+impl<'a> ShadowCaster<'a> {
+    pub fn new(vao: &'a Vao, model_matrix: glam::Mat4) -> Self {
+        Self {
+            vao,
+            model_matrix,
+            shader: None,
+        }
+    }
+
+    pub fn shader(mut self, shader: &'a GlslProg) -> Self {
+        self.shader = Some(shader);
+        self
+    }
+}
+
+/// Depth map of a directional ( cascaded ) or spot light, rendered in to a depth texture
+/// array attached to an `Fbo`. Each cascade is a layer of the array.
+///
+/// ```ignore
+/// let mut shadows = glh::ShadowMap::directional(gl, glam::vec3(-0.4, -1.0, -0.6));
+/// let shader = glh::StockShader::new().lambert().receive_shadows(&shadows).build(gl);
+///
+/// // every frame
+/// shadows.update(&camera.get_view_matrix(), &camera.get_perspective_matrix());
+/// shadows.render(gl, &[glh::ShadowCaster::new(&vao, model)]);
+/// shader.bind(gl);
+/// shadows.apply(gl, &shader);
+/// ```
+pub struct ShadowMap {
+    light: ShadowLight,
+    settings: ShadowSettings,
+
+    fbo: Fbo,
+    depth_texture: Texture,
+    depth_shader: GlslProg,
+
+    view_matrices: Vec<glam::Mat4>,
+    projection_matrices: Vec<glam::Mat4>,
+    cascade_splits: Vec<f32>, // far view space depth of each cascade
+}
+
+impl ShadowMap {
+    pub fn directional(gl: &glow::Context, direction: glam::Vec3) -> Self {
+        Self::new(
+            gl,
+            ShadowLight::Directional { direction },
+            ShadowSettings::default(),
+        )
+    }
+
+    pub fn spot(
+        gl: &glow::Context,
+        position: glam::Vec3,
+        direction: glam::Vec3,
+        angle: f32,
+        range: f32,
+    ) -> Self {
+        Self::new(
+            gl,
+            ShadowLight::Spot {
+                position,
+                direction,
+                angle,
+                range,
+            },
+            ShadowSettings::default(),
+        )
+    }
+
+    pub fn new(gl: &glow::Context, light: ShadowLight, mut settings: ShadowSettings) -> Self {
+        settings.cascades = match light {
+            ShadowLight::Directional { .. } => settings.cascades.clamp(1, MAX_SHADOW_CASCADES),
+            ShadowLight::Spot { .. } => 1,
+        };
+        settings.pcf_radius = settings.pcf_radius.max(0);
+
+        let max_size = unsafe { gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) };
+        settings.resolution = settings.resolution.clamp(1, max_size);
+
+        let mut texture_settings = TextureSettings::default().wrap(glow::CLAMP_TO_EDGE);
+        texture_settings.internal_format = glow::DEPTH_COMPONENT24;
+        texture_settings.format = glow::DEPTH_COMPONENT;
+        texture_settings.data_type = glow::UNSIGNED_INT;
+        let depth_texture = Texture::new_2d_array_from_data(
+            gl,
+            None,
+            settings.resolution,
+            settings.resolution,
+            settings.cascades as i32,
+            texture_settings,
+        );
+        unsafe {
+            // linear filtering of a compared texture gives 2x2 pcf for free
+            depth_texture.bind(gl);
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D_ARRAY,
+                glow::TEXTURE_COMPARE_MODE,
+                glow::COMPARE_REF_TO_TEXTURE as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D_ARRAY,
+                glow::TEXTURE_COMPARE_FUNC,
+                glow::LEQUAL as i32,
+            );
+            depth_texture.unbind(gl);
+        }
+
+        // depth only, render attaches the layer of each cascade
+        let fbo = Fbo::new(
+            gl,
+            FboSettings {
+                width: settings.resolution,
+                height: settings.resolution,
+                depth: true,
+                initialize_default_texture: false,
+            },
+            texture_settings,
+        );
+        unsafe {
+            let previous_framebuffer = current_framebuffer(gl);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, fbo.fbo_handle);
+            gl.draw_buffers(&[glow::NONE]);
+            gl.read_buffer(glow::NONE);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, previous_framebuffer);
+        }
+
+        let mut shadow_map = Self {
+            light,
+            settings,
+            fbo,
+            depth_texture,
+            depth_shader: StockShader::new().build(gl),
+            view_matrices: Vec::new(),
+            projection_matrices: Vec::new(),
+            cascade_splits: Vec::new(),
+        };
+
+        // spot lights don't depend on the camera, directional ones wait for update
+        if !shadow_map.is_directional() {
+            shadow_map.update(&glam::Mat4::IDENTITY, &glam::Mat4::IDENTITY);
+        }
+        shadow_map
+    }
+
+    pub fn set_light(&mut self, light: ShadowLight) {
+        assert_eq!(
+            matches!(light, ShadowLight::Directional { .. }),
+            self.is_directional(),
+            "a shadow map can't change between directional and spot lights"
+        );
+        self.light = light;
+    }
+
+    pub fn get_light(&self) -> ShadowLight {
+        self.light
+    }
+
+    pub fn get_settings(&self) -> &ShadowSettings {
+        &self.settings
+    }
+
+    // the resolution and number of cascades are fixed at creation
+    pub fn set_filtering(&mut self, bias: f32, normal_bias: f32, pcf_radius: i32) {
+        self.settings.bias = bias;
+        self.settings.normal_bias = normal_bias;
+        self.settings.pcf_radius = pcf_radius.max(0);
+    }
+
+    pub fn is_directional(&self) -> bool {
+        matches!(self.light, ShadowLight::Directional { .. })
+    }
+
+    pub fn get_num_of_cascades(&self) -> u32 {
+        self.settings.cascades
+    }
+
+    pub fn get_depth_texture(&self) -> &Texture {
+        &self.depth_texture
+    }
+
+    pub fn get_cascade_splits(&self) -> &[f32] {
+        &self.cascade_splits
+    }
+
+    // fits the light frustums to the camera, call it when the camera or the light moves.
+    // the camera projection has to come from perspective_rh_gl
+    pub fn update(&mut self, camera_view: &glam::Mat4, camera_projection: &glam::Mat4) {
+        self.view_matrices.clear();
+        self.projection_matrices.clear();
+        self.cascade_splits.clear();
+
+        match self.light {
+            ShadowLight::Directional { direction } => {
+                let (near, far) = perspective_near_far(camera_projection);
+                let far = far.min(self.settings.max_distance).max(near);
+                let splits = cascade_splits(
+                    near,
+                    far,
+                    self.settings.cascades,
+                    self.settings.split_lambda,
+                );
+
+                let corners = frustum_corners(camera_projection);
+                let mut split_near = near;
+                for split_far in splits {
+                    let slice = frustum_slice(&corners, camera_view, split_near, split_far);
+                    let (view, projection) = fit_cascade(
+                        &slice,
+                        direction,
+                        self.settings.resolution,
+                        self.settings.caster_margin,
+                    );
+                    self.view_matrices.push(view);
+                    self.projection_matrices.push(projection);
+                    self.cascade_splits.push(split_far);
+                    split_near = split_far;
+                }
+            }
+            ShadowLight::Spot {
+                position,
+                direction,
+                angle,
+                range,
+            } => {
+                let direction = direction.normalize();
+                let view =
+                    glam::Mat4::look_at_rh(position, position + direction, up_vector(direction));
+                let projection = glam::Mat4::perspective_rh_gl(
+                    angle.clamp(0.01, 3.1),
+                    1.0,
+                    (range * 0.001).max(0.01),
+                    range,
+                );
+                self.view_matrices.push(view);
+                self.projection_matrices.push(projection);
+                self.cascade_splits.push(range);
+            }
+        }
+    }
+
+    // renders the depth of the casters in to every cascade, then restores the framebuffer,
+    // the viewport and the depth state
+    pub fn render(&self, gl: &glow::Context, casters: &[ShadowCaster]) {
+        let mut viewport = [0; 4];
+        let previous_framebuffer = current_framebuffer(gl);
+        let (depth_test, depth_mask) = unsafe {
+            gl.get_parameter_i32_slice(glow::VIEWPORT, &mut viewport);
+            let depth_test = gl.is_enabled(glow::DEPTH_TEST);
+            let depth_mask = gl.get_parameter_i32(glow::DEPTH_WRITEMASK) != 0;
+
+            self.fbo.bind(gl);
+            gl.viewport(0, 0, self.settings.resolution, self.settings.resolution);
+            gl.depth_mask(true);
+            gl.enable(glow::DEPTH_TEST);
+            gl.enable(glow::POLYGON_OFFSET_FILL);
+            gl.polygon_offset(1.5, 4.0);
+            (depth_test, depth_mask)
+        };
+
+        for cascade in 0..self.view_matrices.len() {
+            unsafe {
+                gl.framebuffer_texture_layer(
+                    glow::FRAMEBUFFER,
+                    glow::DEPTH_ATTACHMENT,
+                    self.depth_texture.handle,
+                    0,
+                    cascade as i32,
+                );
+                gl.clear(glow::DEPTH_BUFFER_BIT);
+            }
+
+            for caster in casters {
+                let shader = caster.shader.unwrap_or(&self.depth_shader);
+                shader.bind(gl);
+                shader.set_uniform_mat4(
+                    gl,
+                    StockShader::uniform_name_perspective_matrix(),
+                    &self.projection_matrices[cascade],
+                );
+                shader.set_view_matrix(gl, &self.view_matrices[cascade]);
+                shader.set_model_matrix(gl, &caster.model_matrix);
+
+                if caster.vao.get_num_of_instances() > 0 {
+                    caster
+                        .vao
+                        .draw_instanced(gl, caster.vao.get_num_of_instances() as i32);
+                } else {
+                    caster.vao.draw(gl);
+                }
+                shader.unbind(gl);
+            }
+        }
+
+        unsafe {
+            gl.disable(glow::POLYGON_OFFSET_FILL);
+            if !depth_test {
+                gl.disable(glow::DEPTH_TEST);
+            }
+            gl.depth_mask(depth_mask);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, previous_framebuffer);
+            gl.viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+    }
+
+    // world to [0, 1] shadow map coordinates of each cascade
+    pub fn get_shadow_matrices(&self) -> Vec<glam::Mat4> {
+        let bias = glam::Mat4::from_translation(glam::Vec3::splat(0.5))
+            * glam::Mat4::from_scale(glam::Vec3::splat(0.5));
+
+        (0..self.view_matrices.len())
+            .map(|i| bias * self.projection_matrices[i] * self.view_matrices[i])
+            .collect()
+    }
+
+    // binds the depth texture and sets the shadow uniforms, the program has to be bound
+    pub fn apply(&self, gl: &glow::Context, prog: &GlslProg) {
+        prog.bind_texture(
+            gl,
+            StockShader::uniform_name_shadow_map(),
+            &self.depth_texture,
+        );
+
+        let matrices = StockShader::uniform_name_shadow_matrices();
+        let splits = StockShader::uniform_name_shadow_cascade_splits();
+        for (i, matrix) in self.get_shadow_matrices().iter().enumerate() {
+            prog.try_set(gl, &format!("{}[{}]", matrices, i), matrix);
+            prog.try_set(gl, &format!("{}[{}]", splits, i), self.cascade_splits[i]);
+        }
+
+        prog.try_set(
+            gl,
+            StockShader::uniform_name_shadow_bias(),
+            self.settings.bias,
+        );
+        prog.try_set(
+            gl,
+            StockShader::uniform_name_shadow_normal_bias(),
+            self.settings.normal_bias,
+        );
+        prog.try_set(
+            gl,
+            StockShader::uniform_name_shadow_pcf_radius(),
+            self.settings.pcf_radius,
+        );
+    }
+
+    pub fn delete(&mut self, gl: &glow::Context) {
+        unsafe {
+            if let Some(handle) = self.fbo.fbo_handle.take() {
+                gl.delete_framebuffer(handle);
+            }
+            if let Some(handle) = self.depth_texture.handle.take() {
+                gl.delete_texture(handle);
+            }
+        }
+        if self.depth_shader.get_handle().is_some() {
+            self.depth_shader.delete(gl);
+        }
+    }
+}
+
+fn up_vector(direction: glam::Vec3) -> glam::Vec3 {
+    if direction.normalize().y.abs() > 0.99 {
+        glam::Vec3::Z
+    } else {
+        glam::Vec3::Y
+    }
+}
+
+// near and far planes of a perspective_rh_gl projection
+fn perspective_near_far(projection: &glam::Mat4) -> (f32, f32) {
+    let a = projection.z_axis.z;
+    let b = projection.w_axis.z;
+    (b / (a - 1.0), b / (a + 1.0))
+}
+
+// far depth of each cascade, blends even and logarithmic splits
+fn cascade_splits(near: f32, far: f32, cascades: u32, lambda: f32) -> Vec<f32> {
+    (1..=cascades)
+        .map(|i| {
+            let t = i as f32 / cascades as f32;
+            let log = near * (far / near).powf(t);
+            let even = near + (far - near) * t;
+            lambda * log + (1.0 - lambda) * even
+        })
+        .collect()
+}
+
+// view space corners of the near ( 0..4 ) and far ( 4..8 ) planes
+fn frustum_corners(projection: &glam::Mat4) -> [glam::Vec3; 8] {
+    let inverse_projection = projection.inverse();
+
+    let mut corners = [glam::Vec3::ZERO; 8];
+    let mut i = 0;
+    for z in [-1.0, 1.0] {
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            corners[i] = inverse_projection.project_point3(glam::vec3(x, y, z));
+            i += 1;
+        }
+    }
+    corners
+}
+
+// world space corners of the part of the frustum between two view depths.
+// the corners are on rays from the eye, so slicing by depth is a lerp in view space
+fn frustum_slice(
+    corners: &[glam::Vec3; 8],
+    camera_view: &glam::Mat4,
+    split_near: f32,
+    split_far: f32,
+) -> [glam::Vec3; 8] {
+    let inverse_view = camera_view.inverse();
+
+    let mut slice = [glam::Vec3::ZERO; 8];
+    for i in 0..4 {
+        let ray = corners[i + 4] - corners[i];
+        let t_near = (-split_near - corners[i].z) / ray.z;
+        let t_far = (-split_far - corners[i].z) / ray.z;
+        slice[i] = inverse_view.transform_point3(corners[i] + ray * t_near);
+        slice[i + 4] = inverse_view.transform_point3(corners[i] + ray * t_far);
+    }
+    slice
+}
+
+// light view and orthographic projection enclosing the slice. a bounding sphere keeps the size
+// constant as the camera turns and the center is snapped to whole texels, so edges don't shimmer
+fn fit_cascade(
+    slice: &[glam::Vec3; 8],
+    direction: glam::Vec3,
+    resolution: i32,
+    caster_margin: f32,
+) -> (glam::Mat4, glam::Mat4) {
+    let direction = direction.normalize();
+    let up = up_vector(direction);
+
+    let center = slice.iter().fold(glam::Vec3::ZERO, |sum, c| sum + *c) / 8.0;
+    let radius = slice
+        .iter()
+        .map(|c| c.distance(center))
+        .fold(0.0f32, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let texel = radius * 2.0 / resolution as f32;
+    let rotation = glam::Mat4::look_at_rh(glam::Vec3::ZERO, direction, up);
+    let mut light_center = rotation.transform_point3(center);
+    light_center.x = (light_center.x / texel).floor() * texel;
+    light_center.y = (light_center.y / texel).floor() * texel;
+    let center = rotation.inverse().transform_point3(light_center);
+
+    let eye = center - direction * (radius + caster_margin);
+    let view = glam::Mat4::look_at_rh(eye, center, up);
+    let projection = glam::Mat4::orthographic_rh_gl(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        0.0,
+        radius * 2.0 + caster_margin,
+    );
+    (view, projection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_end_at_far() {
+        let splits = cascade_splits(0.1, 100.0, 3, 0.75);
+        assert_eq!(splits.len(), 3);
+        assert!(splits.windows(2).all(|w| w[0] < w[1]));
+        assert!((splits[2] - 100.0).abs() < 1e-3);
+
+        let even = cascade_splits(10.0, 100.0, 3, 0.0);
+        assert!((even[0] - 40.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn near_far_from_projection() {
+        let projection = glam::Mat4::perspective_rh_gl(1.0, 1.5, 0.5, 250.0);
+        let (near, far) = perspective_near_far(&projection);
+        assert!((near - 0.5).abs() < 1e-3);
+        assert!((far - 250.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn cascade_contains_slice() {
+        let view =
+            glam::Mat4::look_at_rh(glam::vec3(3.0, 2.0, 5.0), glam::Vec3::ZERO, glam::Vec3::Y);
+        let projection = glam::Mat4::perspective_rh_gl(1.0, 1.5, 0.1, 50.0);
+        let slice = frustum_slice(&frustum_corners(&projection), &view, 2.0, 10.0);
+
+        // slice corners are at the requested view depths
+        assert!((-view.transform_point3(slice[0]).z - 2.0).abs() < 1e-3);
+        assert!((-view.transform_point3(slice[4]).z - 10.0).abs() < 1e-3);
+
+        let (light_view, light_projection) =
+            fit_cascade(&slice, glam::vec3(-0.4, -1.0, -0.6), 1024, 10.0);
+        for corner in slice.iter() {
+            let ndc = (light_projection * light_view).project_point3(*corner);
+            // snapping the center moves it by up to a texel
+            assert!(
+                ndc.abs().max_element() <= 1.0 + 2.0 / 1024.0,
+                "{:?} outside {:?}",
+                corner,
+                ndc
+            );
+        }
+    }
+}
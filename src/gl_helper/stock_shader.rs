@@ -1,12 +1,37 @@
 use crate::gl_helper::glsl_prog::GlslProg;
+use crate::gl_helper::shader_error::ShaderError;
+use crate::gl_helper::shader_preprocessor;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::gl_helper::ProgramCache;
+use crate::gl_helper::pbr_material::{PbrMaps, PbrMaterial};
+use crate::gl_helper::shadow_map::ShadowMap;
 
 use glow;
 use std::string::String;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lighting {
+    Unlit,
+    Lambert, // diffuse only
+    Phong,   // diffuse and blinn-phong specular
+    Pbr,     // metallic-roughness cook-torrance, see PbrMaterial
+}
+
 #[derive(Debug, Clone)]
 pub struct StockShader {
     texture: (bool, bool), //first element is if shader has texture and second is for flipping the texture
     color: bool,
+    lighting: Lighting,
+    directional_lights: u32,
+    point_lights: u32,
+    pbr_maps: PbrMaps,
+    image_based_lighting: bool,
+    instance_translation: bool,
+    instance_transform: bool,
+    instance_color: bool,
+    instance_uv_offset: bool,
+    shadow_cascades: u32, // 0 when not receiving shadows
+    shadow_spot: bool,
 }
 
 impl StockShader {
@@ -14,6 +39,17 @@ impl StockShader {
         StockShader {
             texture: (false, false),
             color: false,
+            lighting: Lighting::Unlit,
+            directional_lights: 1,
+            point_lights: 0,
+            pbr_maps: PbrMaps::default(),
+            image_based_lighting: false,
+            instance_translation: false,
+            instance_transform: false,
+            instance_color: false,
+            instance_uv_offset: false,
+            shadow_cascades: 0,
+            shadow_spot: false,
         }
     }
 
@@ -27,12 +63,89 @@ impl StockShader {
         self.clone()
     }
 
-    pub fn get_vertex_string(&self) -> std::string::String {
-        #[cfg(not(target_arch = "wasm32"))]
-        let shader_version = "#version 400";
+    // Instancing ---
+    // per instance attributes, applied on top of uModelMatrix. with both translation and transform
+    // an instance is transformed first and then translated
+    pub fn instanced_translation(&mut self) -> StockShader {
+        self.instance_translation = true;
+        self.clone()
+    }
+
+    pub fn instanced_transform(&mut self) -> StockShader {
+        self.instance_transform = true;
+        self.clone()
+    }
+
+    // multiplies uColor and the vertex color
+    pub fn instanced_color(&mut self) -> StockShader {
+        self.instance_color = true;
+        self.clone()
+    }
+
+    // added to the texture coordinates, ie: to pick a sprite from a sheet
+    pub fn instanced_uv_offset(&mut self) -> StockShader {
+        self.instance_uv_offset = true;
+        self.clone()
+    }
+
+    pub fn is_instanced(&self) -> bool {
+        self.instance_translation
+            || self.instance_transform
+            || self.instance_color
+            || self.instance_uv_offset
+    }
+
+    // true when the fragment shader reads vColor
+    fn has_vertex_color(&self) -> bool {
+        self.color || self.instance_color
+    }
+
+    // per fragment diffuse lighting, reads the normal attribute
+    pub fn lambert(&mut self) -> StockShader {
+        self.lighting = Lighting::Lambert;
+        self.clone()
+    }
 
-        #[cfg(target_arch = "wasm32")]
-        let shader_version = "#version 300 es";
+    // lambert plus specular highlights
+    pub fn phong(&mut self) -> StockShader {
+        self.lighting = Lighting::Phong;
+        self.clone()
+    }
+
+    // glTF 2.0 metallic-roughness, `maps` are the textures the material samples ( PbrMaterial::maps ),
+    // any map needs texture coordinates
+    pub fn pbr(&mut self, maps: PbrMaps) -> StockShader {
+        self.lighting = Lighting::Pbr;
+        self.pbr_maps = maps;
+        self.clone()
+    }
+
+    // pbr ambient from an irradiance and a prefiltered environment cube map instead of uAmbientColor
+    pub fn image_based_lighting(&mut self) -> StockShader {
+        self.image_based_lighting = true;
+        self.clone()
+    }
+
+    // shadows of the map on its light ( see ShadowLight ), only lit shaders receive shadows
+    pub fn receive_shadows(&mut self, shadow_map: &ShadowMap) -> StockShader {
+        self.shadow_cascades = shadow_map.get_num_of_cascades();
+        self.shadow_spot = !shadow_map.is_directional();
+        self.clone()
+    }
+
+    // number of directional and point lights of the lit shaders, one directional light by default
+    pub fn lights(&mut self, directional: u32, point: u32) -> StockShader {
+        self.directional_lights = directional;
+        self.point_lights = point;
+        self.clone()
+    }
+
+    pub fn get_lighting(&self) -> Lighting {
+        self.lighting
+    }
+
+    pub fn get_vertex_string(&self) -> std::string::String {
+        let shader_version = shader_preprocessor::version_directive();
 
         let mut color_layout = String::from("");
         let mut texture_layout = String::from("");
@@ -49,7 +162,20 @@ impl StockShader {
             color_main = format!("vColor = {};", StockShader::attrib_name_color());
         }
 
-        if self.texture.0 {
+        if self.instance_color {
+            if self.color {
+                color_layout += &format!("\n        in vec4 {};", StockShader::attrib_name_instance_color());
+                color_main += &format!("\n            vColor *= {};", StockShader::attrib_name_instance_color());
+            } else {
+                color_layout = format!(
+                    "in vec4 {};\n            out vec4 vColor;",
+                    StockShader::attrib_name_instance_color()
+                );
+                color_main = format!("vColor = {};", StockShader::attrib_name_instance_color());
+            }
+        }
+
+        if self.texture.0 || self.pbr_maps.any() {
             texture_layout = format!(
                 "in vec2 {};\n            out vec2 textureCoord;",
                 StockShader::attrib_name_texture_coords()
@@ -58,13 +184,76 @@ impl StockShader {
                 "textureCoord = {};",
                 StockShader::attrib_name_texture_coords()
             );
+
+            if self.instance_uv_offset {
+                texture_layout += &format!(
+                    "\n        in vec2 {};",
+                    StockShader::attrib_name_instance_uv_offset()
+                );
+                texture_main += &format!(
+                    "\n            textureCoord += {};",
+                    StockShader::attrib_name_instance_uv_offset()
+                );
+            }
+        }
+
+        // modelMatrix is uModelMatrix with the per instance translation and transform applied
+        let mut instance_layout = String::from("");
+        let mut instance_main = format!(
+            "mat4 modelMatrix = {};",
+            StockShader::uniform_name_model_matrix()
+        );
+        if self.instance_translation {
+            instance_layout += &format!("in vec3 {};", StockShader::attrib_name_instance_translation());
+            instance_main += &format!(
+                "\n            modelMatrix[3] = modelMatrix * vec4({}, 1.0);",
+                StockShader::attrib_name_instance_translation()
+            );
+        }
+        if self.instance_transform {
+            instance_layout += &format!("\n        in mat4 {};", StockShader::attrib_name_instance_transform());
+            instance_main += &format!(
+                "\n            modelMatrix = modelMatrix * {};",
+                StockShader::attrib_name_instance_transform()
+            );
+        }
+
+        let mut lighting_layout = String::from("");
+        let mut lighting_main = String::from("");
+
+        // lighting is done in world space, the normal matrix is the inverse transpose of the model matrix
+        if self.lighting != Lighting::Unlit {
+            lighting_layout = format!(
+                "in vec3 {};
+        out vec3 vNormal;
+        out vec3 vWorldPosition;
+        out vec3 vCameraPosition;",
+                StockShader::attrib_name_normal()
+            );
+            if self.shadow_cascades > 0 {
+                lighting_layout += "\n        out float vViewDepth;";
+            }
+            lighting_main = format!(
+                "mat3 normalMatrix = transpose(inverse(mat3(modelMatrix)));
+            vNormal = normalMatrix * {normal};
+            vWorldPosition = (modelMatrix * vec4({position}, 1.0)).xyz;
+            vCameraPosition = inverse({view})[3].xyz;",
+                view = StockShader::uniform_name_view_matrix(),
+                normal = StockShader::attrib_name_normal(),
+                position = StockShader::attrib_name_position()
+            );
+            if self.shadow_cascades > 0 {
+                lighting_main += &format!(
+                    "\n            vViewDepth = -({} * vec4(vWorldPosition, 1.0)).z;",
+                    StockShader::uniform_name_view_matrix()
+                );
+            }
         }
 
         let position_main = format!(
-            "gl_Position = {} * {} * {} * vec4({}, 1.0);",
+            "gl_Position = {} * {} * modelMatrix * vec4({}, 1.0);",
             StockShader::uniform_name_perspective_matrix(),
             StockShader::uniform_name_view_matrix(),
-            StockShader::uniform_name_model_matrix(),
             StockShader::attrib_name_position()
         );
         
@@ -80,11 +269,15 @@ impl StockShader {
         in vec3 inPosition;
         {} // color_layout
         {} // texture_layout
+        {} // instance_layout
+        {} // lighting_layout
 
         void main()
         {{  
             {} // color_main
             {} // texture_main
+            {} // instance_main
+            {} // lighting_main
             {} // position_main
         }}
         ",
@@ -95,8 +288,12 @@ impl StockShader {
             StockShader::uniform_name_view_matrix(),
             color_layout,
             texture_layout,
+            instance_layout,
+            lighting_layout,
             color_main,
             texture_main,
+            instance_main,
+            lighting_main,
             position_main
         );
 
@@ -104,11 +301,11 @@ impl StockShader {
     }
 
     pub fn get_frag_string(&self) -> std::string::String {
-        #[cfg(not(target_arch = "wasm32"))]
-        let shader_version = "#version 400";
+        if self.lighting == Lighting::Pbr {
+            return self.get_pbr_frag_string();
+        }
 
-        #[cfg(target_arch = "wasm32")]
-        let shader_version = "#version 300 es";
+        let shader_version = shader_preprocessor::version_directive();
 
         let mut sampler_2d = String::from("");
         let mut main_texture_coord = String::from("");
@@ -134,10 +331,12 @@ impl StockShader {
             }
         }
 
-        if self.color {
+        if self.has_vertex_color() {
             main_vertex_color = format!("{} * ", "vColor");
         }
 
+        let (lighting_layout, lighting_main) = self.get_frag_lighting_strings();
+
         let frag_shader = format!(
             "{}
         precision mediump float;
@@ -147,30 +346,553 @@ impl StockShader {
         {} // sampler
         in vec2 textureCoord; // in texture coords
         in vec4 vColor; //in vec4 vertexColor;
+        {} // lighting_layout
 
 
         out vec4 Color;
         void main()
         {{
             Color = {} {} uColor;
+            {} // lighting_main
         }}",
-            shader_version, sampler_2d, main_vertex_color, main_texture_coord
+            shader_version,
+            sampler_2d,
+            lighting_layout,
+            main_vertex_color,
+            main_texture_coord,
+            lighting_main
         );
 
         frag_shader
     }
 
+    // ( declarations, main body ) of the lighting, empty when unlit
+    fn get_frag_lighting_strings(&self) -> (String, String) {
+        if self.lighting == Lighting::Unlit {
+            return (String::from(""), String::from(""));
+        }
+
+        let mut specular_uniforms = String::from("");
+        let mut phong = String::from("");
+        if self.lighting == Lighting::Phong {
+            specular_uniforms = format!(
+                "uniform vec3 {};
+        uniform float {};",
+                StockShader::uniform_name_specular_color(),
+                StockShader::uniform_name_shininess()
+            );
+            phong = format!(
+                "vec3 H = normalize(L + V);
+            specular += radiance * {} * pow(max(dot(N, H), 0.0), {}) * step(0.0, NdotL);",
+                StockShader::uniform_name_specular_color(),
+                StockShader::uniform_name_shininess()
+            );
+        }
+
+        let mut layout = format!(
+            "
+        in vec3 vNormal;
+        in vec3 vWorldPosition;
+        in vec3 vCameraPosition;
+
+        uniform vec3 {ambient};
+        {specular_uniforms}
+
+        // diffuse and specular of one light, specular is left at 0 for lambert
+        void addLight(vec3 N, vec3 V, vec3 L, vec3 radiance, inout vec3 diffuse, inout vec3 specular)
+        {{
+            float NdotL = dot(N, L);
+            diffuse += radiance * max(NdotL, 0.0);
+            {phong}
+        }}",
+            ambient = StockShader::uniform_name_ambient_color(),
+            specular_uniforms = specular_uniforms,
+            phong = phong
+        );
+
+        let mut main = String::from(
+            "vec3 N = normalize(vNormal);
+            N = gl_FrontFacing ? N : -N;
+            vec3 V = normalize(vCameraPosition - vWorldPosition);
+            vec3 diffuse = vec3(0.0);
+            vec3 specular = vec3(0.0);",
+        );
+
+        let (lights_layout, lights_main) = self.get_lights_strings();
+        layout += &lights_layout;
+        main += &lights_main;
+
+        main += &format!(
+            "
+            Color = vec4(Color.rgb * ({} + diffuse) + specular, Color.a);",
+            StockShader::uniform_name_ambient_color()
+        );
+
+        (layout, main)
+    }
+
+    // uColor is the base color factor, output is tone mapped and gamma encoded
+    fn get_pbr_frag_string(&self) -> String {
+        let maps = &self.pbr_maps;
+        let mut layout = String::from("");
+        let mut main = String::from("");
+
+        if maps.base_color {
+            layout += &format!("uniform sampler2D {};\n", StockShader::uniform_name_base_color_map());
+            main += &format!(
+                "baseColor *= texture({}, textureCoord);\n",
+                StockShader::uniform_name_base_color_map()
+            );
+        }
+        if self.has_vertex_color() {
+            main += "baseColor *= vColor;\n";
+        }
+        if maps.metallic_roughness {
+            // glTF packs roughness in green and metallic in blue
+            layout += &format!(
+                "uniform sampler2D {};\n",
+                StockShader::uniform_name_metallic_roughness_map()
+            );
+            main += &format!(
+                "vec4 metallicRoughness = texture({}, textureCoord);
+            roughness *= metallicRoughness.g;
+            metallic *= metallicRoughness.b;\n",
+                StockShader::uniform_name_metallic_roughness_map()
+            );
+        }
+        main += "metallic = clamp(metallic, 0.0, 1.0);
+            roughness = clamp(roughness, 0.04, 1.0);
+            alpha = roughness * roughness;
+            albedo = baseColor.rgb;
+            f0 = mix(vec3(0.04), albedo, metallic);
+
+            vec3 N = normalize(vNormal);
+            N = gl_FrontFacing ? N : -N;\n";
+        if maps.normal {
+            layout += &format!(
+                "uniform sampler2D {};
+        uniform float {};\n",
+                StockShader::uniform_name_normal_map(),
+                StockShader::uniform_name_normal_scale()
+            );
+            main += &format!(
+                "vec3 tangentNormal = texture({}, textureCoord).xyz * 2.0 - 1.0;
+            tangentNormal.xy *= {};
+            N = normalize(cotangentFrame(N, vWorldPosition, textureCoord) * tangentNormal);\n",
+                StockShader::uniform_name_normal_map(),
+                StockShader::uniform_name_normal_scale()
+            );
+        }
+        main += "vec3 V = normalize(vCameraPosition - vWorldPosition);
+            vec3 diffuse = vec3(0.0);
+            vec3 specular = vec3(0.0);";
+
+        let (lights_layout, lights_main) = self.get_lights_strings();
+        layout += &lights_layout;
+        main += &lights_main;
+
+        main += "\nfloat occlusion = 1.0;\n";
+        if maps.occlusion {
+            layout += &format!(
+                "\nuniform sampler2D {};
+        uniform float {};\n",
+                StockShader::uniform_name_occlusion_map(),
+                StockShader::uniform_name_occlusion_strength()
+            );
+            main += &format!(
+                "occlusion = 1.0 + {} * (texture({}, textureCoord).r - 1.0);\n",
+                StockShader::uniform_name_occlusion_strength(),
+                StockShader::uniform_name_occlusion_map()
+            );
+        }
+
+        if self.image_based_lighting {
+            layout += &format!(
+                "\nuniform samplerCube {irradiance};
+        uniform samplerCube {prefiltered};
+        uniform float {mip_levels};
+        uniform float {intensity};\n",
+                irradiance = StockShader::uniform_name_irradiance_map(),
+                prefiltered = StockShader::uniform_name_prefiltered_map(),
+                mip_levels = StockShader::uniform_name_prefiltered_mip_levels(),
+                intensity = StockShader::uniform_name_environment_intensity()
+            );
+            main += &format!(
+                "float NdotV = clamp(abs(dot(N, V)), 0.001, 1.0);
+            vec3 irradiance = texture({irradiance}, N).rgb;
+            vec3 prefiltered = textureLod({prefiltered}, reflect(-V, N), roughness * {mip_levels}).rgb;
+            vec3 ambient = irradiance * albedo * (1.0 - metallic) + prefiltered * envBrdfApprox(f0, roughness, NdotV);
+            ambient *= {intensity};\n",
+                irradiance = StockShader::uniform_name_irradiance_map(),
+                prefiltered = StockShader::uniform_name_prefiltered_map(),
+                mip_levels = StockShader::uniform_name_prefiltered_mip_levels(),
+                intensity = StockShader::uniform_name_environment_intensity()
+            );
+        } else {
+            main += &format!(
+                "vec3 ambient = {} * albedo;\n",
+                StockShader::uniform_name_ambient_color()
+            );
+        }
+
+        main += &format!(
+            "vec3 emissive = {};\n",
+            StockShader::uniform_name_emissive_factor()
+        );
+        if maps.emissive {
+            layout += &format!("uniform sampler2D {};\n", StockShader::uniform_name_emissive_map());
+            main += &format!(
+                "emissive *= texture({}, textureCoord).rgb;\n",
+                StockShader::uniform_name_emissive_map()
+            );
+        }
+
+        format!(
+            "{version}
+        precision highp float;
+
+        in vec2 textureCoord;
+        in vec4 vColor;
+        in vec3 vNormal;
+        in vec3 vWorldPosition;
+        in vec3 vCameraPosition;
+
+        uniform vec4 {color};
+        uniform float {metallic};
+        uniform float {roughness};
+        uniform vec3 {emissive};
+        uniform vec3 {ambient};
+        uniform float {exposure};
+        {layout}
+
+        const float PI = 3.14159265359;
+
+        // material of the current fragment, read by addLight
+        vec3 albedo;
+        vec3 f0;
+        float metallic;
+        float alpha;
+
+        float distributionGGX(float NdotH)
+        {{
+            float a2 = alpha * alpha;
+            float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+            return a2 / (PI * d * d);
+        }}
+
+        // height correlated smith, the form used by the glTF spec
+        float visibilitySmithGGX(float NdotL, float NdotV)
+        {{
+            float a2 = alpha * alpha;
+            float ggxV = NdotL * sqrt(NdotV * NdotV * (1.0 - a2) + a2);
+            float ggxL = NdotV * sqrt(NdotL * NdotL * (1.0 - a2) + a2);
+            float ggx = ggxV + ggxL;
+            return ggx > 0.0 ? 0.5 / ggx : 0.0;
+        }}
+
+        vec3 fresnelSchlick(float VdotH)
+        {{
+            return f0 + (1.0 - f0) * pow(1.0 - VdotH, 5.0);
+        }}
+
+        // cook-torrance of one light
+        void addLight(vec3 N, vec3 V, vec3 L, vec3 radiance, inout vec3 diffuse, inout vec3 specular)
+        {{
+            float NdotL = clamp(dot(N, L), 0.0, 1.0);
+            if (NdotL <= 0.0) {{
+                return;
+            }}
+            vec3 H = normalize(L + V);
+            float NdotV = clamp(abs(dot(N, V)), 0.001, 1.0);
+            float NdotH = clamp(dot(N, H), 0.0, 1.0);
+            float VdotH = clamp(dot(V, H), 0.0, 1.0);
+
+            vec3 F = fresnelSchlick(VdotH);
+            diffuse += (1.0 - F) * (1.0 - metallic) * albedo / PI * radiance * NdotL;
+            specular += F * distributionGGX(NdotH) * visibilitySmithGGX(NdotL, NdotV) * radiance * NdotL;
+        }}
+
+        // normal mapping without tangents, builds the tangent frame from screen space derivatives
+        mat3 cotangentFrame(vec3 N, vec3 p, vec2 uv)
+        {{
+            vec3 dp1 = dFdx(p);
+            vec3 dp2 = dFdy(p);
+            vec2 duv1 = dFdx(uv);
+            vec2 duv2 = dFdy(uv);
+
+            vec3 dp2perp = cross(dp2, N);
+            vec3 dp1perp = cross(N, dp1);
+            vec3 T = dp2perp * duv1.x + dp1perp * duv2.x;
+            vec3 B = dp2perp * duv1.y + dp1perp * duv2.y;
+
+            float invmax = inversesqrt(max(dot(T, T), dot(B, B)));
+            return mat3(T * invmax, B * invmax, N);
+        }}
+
+        // analytic fit of the split sum brdf, saves the lookup texture
+        vec3 envBrdfApprox(vec3 specularColor, float roughness, float NdotV)
+        {{
+            const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+            const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+            vec4 r = roughness * c0 + c1;
+            float a004 = min(r.x * r.x, exp2(-9.28 * NdotV)) * r.x + r.y;
+            vec2 AB = vec2(-1.04, 1.04) * a004 + r.zw;
+            return specularColor * AB.x + AB.y;
+        }}
+
+        out vec4 Color;
+        void main()
+        {{
+            vec4 baseColor = {color};
+            metallic = {metallic};
+            float roughness = {roughness};
+            {main}
+
+            vec3 color = diffuse + specular + ambient * occlusion + emissive;
+
+            // reinhard tone mapping and gamma, the framebuffer isn't sRGB
+            color *= {exposure};
+            color = color / (1.0 + color);
+            Color = vec4(pow(color, vec3(1.0 / 2.2)), baseColor.a);
+        }}",
+            version = shader_preprocessor::version_directive(),
+            color = StockShader::uniform_name_color(),
+            metallic = StockShader::uniform_name_metallic_factor(),
+            roughness = StockShader::uniform_name_roughness_factor(),
+            emissive = StockShader::uniform_name_emissive_factor(),
+            ambient = StockShader::uniform_name_ambient_color(),
+            exposure = StockShader::uniform_name_exposure(),
+            layout = layout,
+            main = main
+        )
+    }
+
+    // light uniforms and the loops calling addLight( N, V, L, radiance, diffuse, specular ) for each
+    fn get_lights_strings(&self) -> (String, String) {
+        let (mut layout, mut main) = self.get_shadow_strings();
+
+        // the shadow map darkens directional light 0, or point light 0 for spot shadows
+        let mut directional_shadow = "";
+        let mut point_shadow = "";
+        if self.shadow_cascades > 0 {
+            if self.shadow_spot {
+                point_shadow = " * (i == 0 ? shadow : 1.0)";
+            } else {
+                directional_shadow = " * (i == 0 ? shadow : 1.0)";
+            }
+        }
+
+        if self.directional_lights > 0 {
+            layout += &format!(
+                "
+        struct DirectionalLight {{
+            vec3 direction; // direction the light travels
+            vec3 color;
+        }};
+        uniform DirectionalLight {}[{}];",
+                StockShader::uniform_name_directional_lights(),
+                self.directional_lights
+            );
+            main += &format!(
+                "
+            for (int i = 0; i < {count}; i++) {{
+                addLight(N, V, normalize(-{lights}[i].direction), {lights}[i].color{shadow}, diffuse, specular);
+            }}",
+                count = self.directional_lights,
+                lights = StockShader::uniform_name_directional_lights(),
+                shadow = directional_shadow
+            );
+        }
+
+        if self.point_lights > 0 {
+            layout += &format!(
+                "
+        struct PointLight {{
+            vec3 position;
+            vec3 color;
+            float range; // no light past this distance
+        }};
+        uniform PointLight {}[{}];",
+                StockShader::uniform_name_point_lights(),
+                self.point_lights
+            );
+            main += &format!(
+                "
+            for (int i = 0; i < {count}; i++) {{
+                vec3 toLight = {lights}[i].position - vWorldPosition;
+                float falloff = clamp(1.0 - length(toLight) / max({lights}[i].range, 0.0001), 0.0, 1.0);
+                addLight(N, V, normalize(toLight), {lights}[i].color * falloff * falloff{shadow}, diffuse, specular);
+            }}",
+                count = self.point_lights,
+                lights = StockShader::uniform_name_point_lights(),
+                shadow = point_shadow
+            );
+        }
+
+        (layout, main)
+    }
+
+    // ( declarations, main body ) of the shadow lookup, sets `shadow` to 0 in shadow and 1 in light
+    fn get_shadow_strings(&self) -> (String, String) {
+        if self.shadow_cascades == 0 {
+            return (String::from(""), String::from(""));
+        }
+
+        // directional shadows end at the last cascade, spot shadows at the light range
+        let past_last_cascade = if self.shadow_spot {
+            String::from("")
+        } else {
+            format!(
+                " || vViewDepth > {}[{} - 1]",
+                StockShader::uniform_name_shadow_cascade_splits(),
+                self.shadow_cascades
+            )
+        };
+
+        let layout = format!(
+            "
+        in float vViewDepth;
+        uniform highp sampler2DArrayShadow {map};
+        uniform mat4 {matrices}[{cascades}];
+        uniform float {splits}[{cascades}];
+        uniform float {bias};
+        uniform float {normal_bias};
+        uniform int {pcf_radius};
+
+        // each cascade is a layer of the shadow map
+        float shadowFactor(vec3 N, vec3 L)
+        {{
+            int cascade = 0;
+            for (int i = 0; i < {cascades} - 1; i++) {{
+                if (vViewDepth > {splits}[i]) {{
+                    cascade = i + 1;
+                }}
+            }}
+
+            // push the receiver along the normal, more at grazing angles, against acne
+            float NdotL = clamp(dot(N, L), 0.0, 1.0);
+            vec3 position = vWorldPosition + N * {normal_bias} * (1.0 - NdotL);
+            vec4 shadowCoord = {matrices}[cascade] * vec4(position, 1.0);
+            shadowCoord.xyz /= shadowCoord.w;
+            if (shadowCoord.w <= 0.0 || shadowCoord.z >= 1.0{past_last_cascade}) {{
+                return 1.0;
+            }}
+
+            // keep the filter inside the map, outside it is lit
+            vec2 texel = 1.0 / vec2(textureSize({map}, 0).xy);
+            vec2 minUv = texel * 0.5;
+            vec2 maxUv = 1.0 - texel * 0.5;
+            if (any(lessThan(shadowCoord.xy, minUv)) || any(greaterThan(shadowCoord.xy, maxUv))) {{
+                return 1.0;
+            }}
+
+            float lit = 0.0;
+            for (int x = -{pcf_radius}; x <= {pcf_radius}; x++) {{
+                for (int y = -{pcf_radius}; y <= {pcf_radius}; y++) {{
+                    vec2 uv = clamp(shadowCoord.xy + vec2(float(x), float(y)) * texel, minUv, maxUv);
+                    lit += texture({map}, vec4(uv, float(cascade), shadowCoord.z - {bias}));
+                }}
+            }}
+            float taps = float((2 * {pcf_radius} + 1) * (2 * {pcf_radius} + 1));
+            return lit / taps;
+        }}",
+            map = StockShader::uniform_name_shadow_map(),
+            matrices = StockShader::uniform_name_shadow_matrices(),
+            splits = StockShader::uniform_name_shadow_cascade_splits(),
+            bias = StockShader::uniform_name_shadow_bias(),
+            normal_bias = StockShader::uniform_name_shadow_normal_bias(),
+            pcf_radius = StockShader::uniform_name_shadow_pcf_radius(),
+            cascades = self.shadow_cascades,
+            past_last_cascade = past_last_cascade
+        );
+
+        let light_direction = if self.shadow_spot && self.point_lights > 0 {
+            format!(
+                "normalize({}[0].position - vWorldPosition)",
+                StockShader::uniform_name_point_lights()
+            )
+        } else if !self.shadow_spot && self.directional_lights > 0 {
+            format!(
+                "normalize(-{}[0].direction)",
+                StockShader::uniform_name_directional_lights()
+            )
+        } else {
+            // the shadowed light doesn't exist
+            return (String::from(""), String::from(""));
+        };
+
+        let main = format!(
+            "
+            float shadow = shadowFactor(N, {});",
+            light_direction
+        );
+
+        (layout, main)
+    }
+
     pub fn build(&self, gl: &glow::Context) -> GlslProg {
+        match self.try_build(gl) {
+            Ok(prog) => prog,
+            Err(err) => panic!("StockShader {:?} failed to build:\n{}", self, err),
+        }
+    }
+
+    pub fn try_build(&self, gl: &glow::Context) -> Result<GlslProg, ShaderError> {
+        let vertex_string = self.get_vertex_string();
+        let frag_string = self.get_frag_string();
+
+        let prog = GlslProg::try_new(gl, vertex_string.as_str(), frag_string.as_str())?;
+        Ok(self.set_default_uniforms(gl, prog))
+    }
+
+    // loads the linked program from the cache when it was built on a previous run
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn try_build_cached(
+        &self,
+        gl: &glow::Context,
+        cache: &ProgramCache,
+    ) -> Result<GlslProg, ShaderError> {
         let vertex_string = self.get_vertex_string();
         let frag_string = self.get_frag_string();
 
-        let prog = GlslProg::new(gl, vertex_string.as_str(), frag_string.as_str());
+        let prog = cache.build(gl, vertex_string.as_str(), frag_string.as_str())?;
+        Ok(self.set_default_uniforms(gl, prog))
+    }
 
-        // set some default values for uniforms
+    fn set_default_uniforms(&self, gl: &glow::Context, prog: GlslProg) -> GlslProg {
         prog.bind(gl);
         prog.set_uniform_4f(gl, StockShader::uniform_name_color(), &[1.0, 1.0, 1.0, 1.0]);
-        prog.unbind(gl);
 
+        // a light from above, so lit geometry looks 3D without any setup
+        if self.lighting != Lighting::Unlit {
+            // with image based lighting the environment maps replace the ambient color
+            if !self.image_based_lighting || self.lighting != Lighting::Pbr {
+                prog.set_ambient_color(gl, glam::vec3(0.15, 0.15, 0.15));
+            }
+            if self.lighting == Lighting::Phong {
+                prog.set_specular(gl, glam::vec3(0.5, 0.5, 0.5), 32.0);
+            }
+
+            // the pbr diffuse is divided by PI, scale its light so both look alike
+            let light_color = if self.lighting == Lighting::Pbr {
+                glam::Vec3::splat(std::f32::consts::PI)
+            } else {
+                glam::Vec3::ONE
+            };
+            if self.directional_lights > 0 {
+                prog.set_directional_light(gl, 0, glam::vec3(-0.4, -1.0, -0.6), light_color);
+            }
+        }
+
+        if self.lighting == Lighting::Pbr {
+            PbrMaterial::default().apply_factors(gl, &prog);
+            prog.set(gl, StockShader::uniform_name_exposure(), 1.0);
+            if self.image_based_lighting {
+                prog.set(gl, StockShader::uniform_name_environment_intensity(), 1.0);
+            }
+        }
+
+        prog.unbind(gl);
         prog
     }
 
@@ -203,4 +925,146 @@ impl StockShader {
     pub fn attrib_name_normal() -> &'static str {
         "inNormal"
    }
+    pub fn attrib_name_instance_translation() -> &'static str {
+        "inInstanceTranslation"
+    }
+    pub fn attrib_name_instance_transform() -> &'static str {
+        "inInstanceTransform"
+    }
+    pub fn attrib_name_instance_color() -> &'static str {
+        "inInstanceColor"
+    }
+    pub fn attrib_name_instance_uv_offset() -> &'static str {
+        "inInstanceUvOffset"
+    }
+    pub fn uniform_name_ambient_color() -> &'static str {
+        "uAmbientColor"
+    }
+    pub fn uniform_name_specular_color() -> &'static str {
+        "uSpecularColor"
+    }
+    pub fn uniform_name_shininess() -> &'static str {
+        "uShininess"
+    }
+    // arrays of structs, e.g. uDirectionalLights[0].direction
+    pub fn uniform_name_directional_lights() -> &'static str {
+        "uDirectionalLights"
+    }
+    pub fn uniform_name_point_lights() -> &'static str {
+        "uPointLights"
+    }
+    // pbr, uColor is the base color factor
+    pub fn uniform_name_metallic_factor() -> &'static str {
+        "uMetallicFactor"
+    }
+    pub fn uniform_name_roughness_factor() -> &'static str {
+        "uRoughnessFactor"
+    }
+    pub fn uniform_name_emissive_factor() -> &'static str {
+        "uEmissiveFactor"
+    }
+    pub fn uniform_name_normal_scale() -> &'static str {
+        "uNormalScale"
+    }
+    pub fn uniform_name_occlusion_strength() -> &'static str {
+        "uOcclusionStrength"
+    }
+    pub fn uniform_name_exposure() -> &'static str {
+        "uExposure"
+    }
+    pub fn uniform_name_base_color_map() -> &'static str {
+        "uBaseColorMap"
+    }
+    pub fn uniform_name_metallic_roughness_map() -> &'static str {
+        "uMetallicRoughnessMap"
+    }
+    pub fn uniform_name_normal_map() -> &'static str {
+        "uNormalMap"
+    }
+    pub fn uniform_name_occlusion_map() -> &'static str {
+        "uOcclusionMap"
+    }
+    pub fn uniform_name_emissive_map() -> &'static str {
+        "uEmissiveMap"
+    }
+    pub fn uniform_name_irradiance_map() -> &'static str {
+        "uIrradianceMap"
+    }
+    pub fn uniform_name_prefiltered_map() -> &'static str {
+        "uPrefilteredMap"
+    }
+    pub fn uniform_name_prefiltered_mip_levels() -> &'static str {
+        "uPrefilteredMipLevels"
+    }
+    pub fn uniform_name_environment_intensity() -> &'static str {
+        "uEnvironmentIntensity"
+    }
+
+    // shadows, see ShadowMap
+    pub fn uniform_name_shadow_map() -> &'static str {
+        "uShadowMap"
+    }
+    pub fn uniform_name_shadow_matrices() -> &'static str {
+        "uShadowMatrices"
+    }
+    pub fn uniform_name_shadow_cascade_splits() -> &'static str {
+        "uShadowCascadeSplits"
+    }
+    pub fn uniform_name_shadow_bias() -> &'static str {
+        "uShadowBias"
+    }
+    pub fn uniform_name_shadow_normal_bias() -> &'static str {
+        "uShadowNormalBias"
+    }
+    pub fn uniform_name_shadow_pcf_radius() -> &'static str {
+        "uShadowPcfRadius"
+    }
+
+    // Fixed attribute locations ---
+    // bound on every GlslProg before linking, so a vao works with any shader using these names
+    pub fn attrib_location_position() -> u32 {
+        0
+    }
+    pub fn attrib_location_color() -> u32 {
+        1
+    }
+    pub fn attrib_location_texture_coords() -> u32 {
+        2
+    }
+    pub fn attrib_location_normal() -> u32 {
+        3
+    }
+    // per instance attributes start at 8, leaving room for custom per vertex attributes
+    pub fn attrib_location_instance_translation() -> u32 {
+        8
+    }
+    pub fn attrib_location_instance_transform() -> u32 {
+        9 // mat4, takes 9 to 12
+    }
+    pub fn attrib_location_instance_color() -> u32 {
+        13
+    }
+    pub fn attrib_location_instance_uv_offset() -> u32 {
+        14
+    }
+
+    pub fn attrib_locations() -> Vec<(&'static str, u32)> {
+        vec![
+            (StockShader::attrib_name_position(), StockShader::attrib_location_position()),
+            (StockShader::attrib_name_color(), StockShader::attrib_location_color()),
+            (StockShader::attrib_name_texture_coords(), StockShader::attrib_location_texture_coords()),
+            (StockShader::attrib_name_normal(), StockShader::attrib_location_normal()),
+            (StockShader::attrib_name_instance_translation(), StockShader::attrib_location_instance_translation()),
+            (StockShader::attrib_name_instance_transform(), StockShader::attrib_location_instance_transform()),
+            (StockShader::attrib_name_instance_color(), StockShader::attrib_location_instance_color()),
+            (StockShader::attrib_name_instance_uv_offset(), StockShader::attrib_location_instance_uv_offset()),
+        ]
+    }
+
+    pub fn attrib_location(name: &str) -> Option<u32> {
+        StockShader::attrib_locations()
+            .iter()
+            .find(|(attrib_name, _)| *attrib_name == name)
+            .map(|(_, loc)| *loc)
+    }
 }
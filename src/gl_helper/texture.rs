@@ -1,7 +1,10 @@
 extern crate image;
 use glow::{self, HasContext};
 use image::EncodableLayout;
+use std::path::Path;
 
+use super::compressed_texture::is_compressed_internal_format;
+use super::texture_error::TextureError;
 use super::Bindable;
 
 #[derive(Clone, Copy)]
@@ -14,9 +17,14 @@ pub struct TextureSettings {
     pub min_filter: u32,
 
     pub wrap_s: u32,
+    pub wrap_t: u32,
     pub wrap_r: u32,
 
     pub target : u32,
+
+    pub mipmaps: bool,    // generated after every upload
+    pub anisotropy: f32,  // 1.0 is off, clamped to what the driver supports
+    pub srgb: bool,       // 8 bit color images loaded with from_path / from_bytes are decoded as sRGB
 }
 
 
@@ -33,8 +41,44 @@ impl TextureSettings {
 
             wrap_r: glow::REPEAT,
             wrap_s: glow::REPEAT,
+            wrap_t: glow::REPEAT,
+
+            mipmaps: false,
+            anisotropy: 1.0,
+            srgb: false,
         }
     }
+
+    pub fn filter(mut self, min_filter: u32, mag_filter: u32) -> Self {
+        self.min_filter = min_filter;
+        self.mag_filter = mag_filter;
+        self
+    }
+
+    pub fn wrap(mut self, wrap: u32) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self.wrap_r = wrap;
+        self
+    }
+
+    // trilinear filtering, the min filter samples the mip chain
+    pub fn mipmaps(mut self) -> Self {
+        self.mipmaps = true;
+        self.min_filter = glow::LINEAR_MIPMAP_LINEAR;
+        self
+    }
+
+    pub fn anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    // for color textures sampled by lit shaders, the shader reads linear values
+    pub fn srgb(mut self) -> Self {
+        self.srgb = true;
+        self
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -42,6 +86,7 @@ pub struct Texture {
     pub handle: Option<glow::Texture>,
     pub width: i32,
     pub height: i32,
+    pub depth: i32, // slices of 3D textures, layers of arrays, 1 for everything else
     pub settings: TextureSettings,
 }
 
@@ -60,6 +105,51 @@ impl Texture {
         )
     }
 
+    // decodes any image the image crate can open, the format is picked from the file
+    pub fn from_path<P: AsRef<Path>>(
+        gl: &glow::Context,
+        path: P,
+        settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        let img = image::open(path)?;
+        Self::from_image(gl, &img, settings)
+    }
+
+    // encoded image data ( png, jpeg, exr, .. ), the format is guessed from the data
+    pub fn from_bytes(
+        gl: &glow::Context,
+        bytes: &[u8],
+        settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        let img = image::load_from_memory(bytes)?;
+        Self::from_image(gl, &img, settings)
+    }
+
+    // uploads the image in the internal format matching its channels and bit depth,
+    // `internal_format`, `format` and `data_type` of the settings are replaced
+    pub fn from_image(
+        gl: &glow::Context,
+        img: &image::DynamicImage,
+        mut settings: TextureSettings,
+    ) -> Result<Self, TextureError> {
+        let converted = backend_compatible(img);
+        let img = converted.as_ref().unwrap_or(img);
+
+        let (internal_format, format, data_type) = upload_format(img, settings.srgb)
+            .ok_or_else(|| TextureError::UnsupportedFormat(format!("{:?}", img.color())))?;
+        settings.internal_format = internal_format;
+        settings.format = format;
+        settings.data_type = data_type;
+
+        Ok(Self::new_from_data(
+            gl,
+            Some(img.as_bytes()),
+            img.width() as i32,
+            img.height() as i32,
+            settings,
+        ))
+    }
+
     pub fn new_from_data(
         gl: &glow::Context,
         data: Option<&[u8]>,
@@ -72,33 +162,154 @@ impl Texture {
             texture_handle = gl.create_texture().expect("Could not create texture");
             gl.bind_texture(settings.target, Some(texture_handle));
 
-            gl.tex_parameter_i32(settings.target, glow::TEXTURE_WRAP_S, settings.wrap_r as i32);
-            gl.tex_parameter_i32(settings.target, glow::TEXTURE_WRAP_R, settings.wrap_s as i32);
+            apply_sampler_settings(gl, &settings);
 
-            gl.tex_parameter_i32(
-                settings.target,
-                glow::TEXTURE_MAG_FILTER,
-                glow::LINEAR as i32,
-            );
-            gl.tex_parameter_i32(
+            // rows of RGB8 or R8 images aren't 4 byte aligned
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.tex_image_2d(
                 settings.target,
-                glow::TEXTURE_MIN_FILTER,
-                glow::LINEAR as i32,
+                0,
+                settings.internal_format as i32,
+                width,
+                height,
+                0,
+                settings.format,
+                settings.data_type,
+                data,
             );
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
 
-            gl.tex_image_2d(
+            if settings.mipmaps && data.is_some() {
+                gl.generate_mipmap(settings.target);
+            }
+
+            gl.bind_texture(settings.target, None);
+        }
+
+        Texture {
+            handle: Some(texture_handle),
+            width,
+            height,
+            depth: 1,
+            settings,
+        }
+    }
+
+    // half float color target, ie: for hdr rendering.
+    // WebGL2 needs EXT_color_buffer_float to render in to it
+    pub fn new_rgba16f(
+        gl: &glow::Context,
+        width: i32,
+        height: i32,
+        mut settings: TextureSettings,
+    ) -> Self {
+        settings.internal_format = glow::RGBA16F;
+        settings.format = glow::RGBA;
+        settings.data_type = glow::HALF_FLOAT;
+        Self::new_from_data(gl, None, width, height, settings)
+    }
+
+    // full float color target, not filterable on WebGL2 without OES_texture_float_linear
+    pub fn new_rgba32f(
+        gl: &glow::Context,
+        width: i32,
+        height: i32,
+        mut settings: TextureSettings,
+    ) -> Self {
+        settings.internal_format = glow::RGBA32F;
+        settings.format = glow::RGBA;
+        settings.data_type = glow::FLOAT;
+        Self::new_from_data(gl, None, width, height, settings)
+    }
+
+    // depth target to attach with Fbo::attach_depth_texture
+    pub fn new_depth24(
+        gl: &glow::Context,
+        width: i32,
+        height: i32,
+        mut settings: TextureSettings,
+    ) -> Self {
+        settings.internal_format = glow::DEPTH_COMPONENT24;
+        settings.format = glow::DEPTH_COMPONENT;
+        settings.data_type = glow::UNSIGNED_INT;
+        Self::new_from_data(gl, None, width, height, settings)
+    }
+
+    pub fn new_depth32f(
+        gl: &glow::Context,
+        width: i32,
+        height: i32,
+        mut settings: TextureSettings,
+    ) -> Self {
+        settings.internal_format = glow::DEPTH_COMPONENT32F;
+        settings.format = glow::DEPTH_COMPONENT;
+        settings.data_type = glow::FLOAT;
+        Self::new_from_data(gl, None, width, height, settings)
+    }
+
+    // volume of `depth` slices, ie: 3D noise or color grading LUTs. data is slice after slice
+    pub fn new_3d_from_data(
+        gl: &glow::Context,
+        data: Option<&[u8]>,
+        width: i32,
+        height: i32,
+        depth: i32,
+        mut settings: TextureSettings,
+    ) -> Self {
+        settings.target = glow::TEXTURE_3D;
+        Self::new_layered_from_data(gl, data, width, height, depth, settings)
+    }
+
+    // `layers` images of the same size, sampled with sampler2DArray. data is layer after layer
+    pub fn new_2d_array_from_data(
+        gl: &glow::Context,
+        data: Option<&[u8]>,
+        width: i32,
+        height: i32,
+        layers: i32,
+        mut settings: TextureSettings,
+    ) -> Self {
+        settings.target = glow::TEXTURE_2D_ARRAY;
+        Self::new_layered_from_data(gl, data, width, height, layers, settings)
+    }
+
+    fn new_layered_from_data(
+        gl: &glow::Context,
+        data: Option<&[u8]>,
+        width: i32,
+        height: i32,
+        depth: i32,
+        settings: TextureSettings,
+    ) -> Self {
+        if let Some(data) = data {
+            check_data_size(&settings, width, height, depth, data);
+        }
+
+        let texture_handle;
+        unsafe {
+            texture_handle = gl.create_texture().expect("Could not create texture");
+            gl.bind_texture(settings.target, Some(texture_handle));
+
+            apply_sampler_settings(gl, &settings);
+
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.tex_image_3d(
                 settings.target,
                 0,
                 settings.internal_format as i32,
                 width,
                 height,
+                depth,
                 0,
                 settings.format,
                 settings.data_type,
                 data,
             );
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
 
-            //gl.tex_image_2d( settings.target, 0, glow::RGB as i32, 2, 2, 0, glow::RGBA as u32, glow::UNSIGNED_BYTE, data );
+            if settings.mipmaps && data.is_some() {
+                gl.generate_mipmap(settings.target);
+            }
 
             gl.bind_texture(settings.target, None);
         }
@@ -107,27 +318,243 @@ impl Texture {
             handle: Some(texture_handle),
             width,
             height,
+            depth,
             settings,
         }
     }
 
+    // replaces the whole texture, every slice or layer of 3D textures and arrays
     pub fn update(&self, gl: &glow::Context, data: &[u8]) {
+        if self.is_layered() {
+            self.update_region_3d(gl, 0, 0, 0, self.width, self.height, self.depth, data);
+        } else {
+            self.update_region(gl, 0, 0, self.width, self.height, data);
+        }
+    }
+
+    // writes a sub rectangle of a 2D texture, data is tightly packed
+    pub fn update_region(
+        &self,
+        gl: &glow::Context,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        data: &[u8],
+    ) {
+        assert!(!self.is_compressed(), "compressed textures can't be updated with pixels");
+        assert!(!self.is_layered(), "use update_region_3d, update_slice or update_layer");
+        assert!(
+            x >= 0 && y >= 0 && x + width <= self.width && y + height <= self.height,
+            "region out of the texture bounds"
+        );
+        check_data_size(&self.settings, width, height, 1, data);
+
         self.bind(gl);
         unsafe {
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
             gl.tex_sub_image_2d(
                 self.settings.target,
                 0,
+                x,
+                y,
+                width,
+                height,
+                self.settings.format,
+                self.settings.data_type,
+                glow::PixelUnpackData::Slice(data),
+            );
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+
+            if self.settings.mipmaps {
+                gl.generate_mipmap(self.settings.target);
+            }
+        }
+        self.unbind(gl);
+    }
+
+    // writes a box of a 3D texture or array, `z` and `depth` count slices or layers
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_region_3d(
+        &self,
+        gl: &glow::Context,
+        x: i32,
+        y: i32,
+        z: i32,
+        width: i32,
+        height: i32,
+        depth: i32,
+        data: &[u8],
+    ) {
+        assert!(!self.is_compressed(), "compressed textures can't be updated with pixels");
+        assert!(self.is_layered(), "use update_region for 2D textures");
+        assert!(
+            x >= 0
+                && y >= 0
+                && z >= 0
+                && x + width <= self.width
+                && y + height <= self.height
+                && z + depth <= self.depth,
+            "region out of the texture bounds"
+        );
+        check_data_size(&self.settings, width, height, depth, data);
+
+        self.bind(gl);
+        unsafe {
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.tex_sub_image_3d(
+                self.settings.target,
                 0,
-                0,
-                self.width,
-                self.height,
+                x,
+                y,
+                z,
+                width,
+                height,
+                depth,
                 self.settings.format,
                 self.settings.data_type,
                 glow::PixelUnpackData::Slice(data),
-            )
+            );
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+
+            if self.settings.mipmaps {
+                gl.generate_mipmap(self.settings.target);
+            }
         }
         self.unbind(gl);
     }
+
+    // replaces one slice of a 3D texture
+    pub fn update_slice(&self, gl: &glow::Context, slice: i32, data: &[u8]) {
+        assert_eq!(self.settings.target, glow::TEXTURE_3D, "update_slice needs a 3D texture");
+        self.update_region_3d(gl, 0, 0, slice, self.width, self.height, 1, data);
+    }
+
+    // replaces one layer of a 2D array texture
+    pub fn update_layer(&self, gl: &glow::Context, layer: i32, data: &[u8]) {
+        assert_eq!(
+            self.settings.target,
+            glow::TEXTURE_2D_ARRAY,
+            "update_layer needs an array texture"
+        );
+        self.update_region_3d(gl, 0, 0, layer, self.width, self.height, 1, data);
+    }
+
+    // loaded from a KTX2 or DDS file without decoding, see Texture::from_compressed
+    pub fn is_compressed(&self) -> bool {
+        is_compressed_internal_format(self.settings.internal_format)
+    }
+
+    pub fn is_layered(&self) -> bool {
+        self.settings.target == glow::TEXTURE_3D || self.settings.target == glow::TEXTURE_2D_ARRAY
+    }
+
+    // re-applies filtering and wrapping after changing `settings`
+    pub fn apply_settings(&self, gl: &glow::Context) {
+        self.bind(gl);
+        unsafe { apply_sampler_settings(gl, &self.settings) };
+        self.unbind(gl);
+    }
+}
+
+// the texture has to be bound
+unsafe fn apply_sampler_settings(gl: &glow::Context, settings: &TextureSettings) {
+    gl.tex_parameter_i32(settings.target, glow::TEXTURE_WRAP_S, settings.wrap_s as i32);
+    gl.tex_parameter_i32(settings.target, glow::TEXTURE_WRAP_T, settings.wrap_t as i32);
+    gl.tex_parameter_i32(settings.target, glow::TEXTURE_WRAP_R, settings.wrap_r as i32);
+
+    gl.tex_parameter_i32(settings.target, glow::TEXTURE_MAG_FILTER, settings.mag_filter as i32);
+    gl.tex_parameter_i32(settings.target, glow::TEXTURE_MIN_FILTER, settings.min_filter as i32);
+
+    if settings.anisotropy > 1.0 && supports_anisotropy(gl) {
+        let max = gl.get_parameter_f32(glow::MAX_TEXTURE_MAX_ANISOTROPY_EXT);
+        gl.tex_parameter_f32(
+            settings.target,
+            glow::TEXTURE_MAX_ANISOTROPY_EXT,
+            settings.anisotropy.min(max),
+        );
+    }
+}
+
+fn supports_anisotropy(gl: &glow::Context) -> bool {
+    let extensions = gl.supported_extensions();
+    extensions.contains("GL_EXT_texture_filter_anisotropic")
+        || extensions.contains("GL_ARB_texture_filter_anisotropic")
+        || extensions.contains("EXT_texture_filter_anisotropic")
+}
+
+// bytes of one pixel uploaded as format and data type, None for packed or unknown types
+pub fn pixel_size(format: u32, data_type: u32) -> Option<usize> {
+    let channels = match format {
+        glow::RED | glow::RED_INTEGER | glow::DEPTH_COMPONENT => 1,
+        glow::RG | glow::RG_INTEGER => 2,
+        glow::RGB | glow::RGB_INTEGER => 3,
+        glow::RGBA | glow::RGBA_INTEGER => 4,
+        _ => return None,
+    };
+    let size = match data_type {
+        glow::UNSIGNED_BYTE | glow::BYTE => 1,
+        glow::UNSIGNED_SHORT | glow::SHORT | glow::HALF_FLOAT => 2,
+        glow::UNSIGNED_INT | glow::INT | glow::FLOAT => 4,
+        _ => return None,
+    };
+    Some(channels * size)
+}
+
+// panics before GL reads past the end of the slice
+fn check_data_size(settings: &TextureSettings, width: i32, height: i32, depth: i32, data: &[u8]) {
+    if let Some(size) = pixel_size(settings.format, settings.data_type) {
+        let expected = size * width as usize * height as usize * depth as usize;
+        assert!(
+            data.len() >= expected,
+            "texture data is {} bytes, {}x{}x{} pixels need {}",
+            data.len(),
+            width,
+            height,
+            depth,
+            expected
+        );
+    }
+}
+
+// a copy the backend can upload, None if the image can be uploaded as is.
+// WebGL2 has no 16 bit normalized formats
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn backend_compatible(img: &image::DynamicImage) -> Option<image::DynamicImage> {
+    match img {
+        image::DynamicImage::ImageLuma16(_) => Some(image::DynamicImage::ImageLuma8(img.to_luma8())),
+        image::DynamicImage::ImageLumaA16(_) => Some(image::DynamicImage::ImageLumaA8(img.to_luma_alpha8())),
+        image::DynamicImage::ImageRgb16(_) => Some(image::DynamicImage::ImageRgb8(img.to_rgb8())),
+        image::DynamicImage::ImageRgba16(_) => Some(image::DynamicImage::ImageRgba8(img.to_rgba8())),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn backend_compatible(_img: &image::DynamicImage) -> Option<image::DynamicImage> {
+    None
+}
+
+// ( internal format, format, data type ) used to upload the image as is, None for layouts GL can't take
+pub fn upload_format(img: &image::DynamicImage, srgb: bool) -> Option<(u32, u32, u32)> {
+    use image::DynamicImage::*;
+
+    let format = match img {
+        ImageLuma8(_) => (glow::R8, glow::RED, glow::UNSIGNED_BYTE),
+        ImageLumaA8(_) => (glow::RG8, glow::RG, glow::UNSIGNED_BYTE),
+        ImageRgb8(_) if srgb => (glow::SRGB8, glow::RGB, glow::UNSIGNED_BYTE),
+        ImageRgb8(_) => (glow::RGB8, glow::RGB, glow::UNSIGNED_BYTE),
+        ImageRgba8(_) if srgb => (glow::SRGB8_ALPHA8, glow::RGBA, glow::UNSIGNED_BYTE),
+        ImageRgba8(_) => (glow::RGBA8, glow::RGBA, glow::UNSIGNED_BYTE),
+        ImageLuma16(_) => (glow::R16, glow::RED, glow::UNSIGNED_SHORT),
+        ImageLumaA16(_) => (glow::RG16, glow::RG, glow::UNSIGNED_SHORT),
+        ImageRgb16(_) => (glow::RGB16, glow::RGB, glow::UNSIGNED_SHORT),
+        ImageRgba16(_) => (glow::RGBA16, glow::RGBA, glow::UNSIGNED_SHORT),
+        ImageRgb32F(_) => (glow::RGB32F, glow::RGB, glow::FLOAT),
+        ImageRgba32F(_) => (glow::RGBA32F, glow::RGBA, glow::FLOAT),
+        _ => return None,
+    };
+    Some(format)
 }
 
 impl Bindable for Texture {
@@ -147,3 +574,36 @@ impl Bindable for Texture {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upload_formats_follow_the_image() {
+        let rgb = image::DynamicImage::new_rgb8(2, 2);
+        assert_eq!(upload_format(&rgb, false), Some((glow::RGB8, glow::RGB, glow::UNSIGNED_BYTE)));
+        assert_eq!(upload_format(&rgb, true), Some((glow::SRGB8, glow::RGB, glow::UNSIGNED_BYTE)));
+
+        // srgb only applies to 8 bit color
+        let luma = image::DynamicImage::new_luma8(2, 2);
+        assert_eq!(upload_format(&luma, true), Some((glow::R8, glow::RED, glow::UNSIGNED_BYTE)));
+
+        let rgba16 = image::DynamicImage::new_rgba16(2, 2);
+        assert_eq!(
+            upload_format(&rgba16, true),
+            Some((glow::RGBA16, glow::RGBA, glow::UNSIGNED_SHORT))
+        );
+
+        let rgba32f = image::DynamicImage::ImageRgba32F(image::Rgba32FImage::new(2, 2));
+        assert_eq!(upload_format(&rgba32f, false), Some((glow::RGBA32F, glow::RGBA, glow::FLOAT)));
+    }
+
+    #[test]
+    fn pixel_sizes() {
+        assert_eq!(pixel_size(glow::RGB, glow::UNSIGNED_BYTE), Some(3));
+        assert_eq!(pixel_size(glow::RGBA, glow::HALF_FLOAT), Some(8));
+        assert_eq!(pixel_size(glow::DEPTH_COMPONENT, glow::FLOAT), Some(4));
+        assert_eq!(pixel_size(glow::RGB, glow::UNSIGNED_INT_10F_11F_11F_REV), None);
+    }
+}
@@ -0,0 +1,845 @@
+use crate::gl_helper::texture::TextureSettings;
+use crate::gl_helper::texture_error::TextureError;
+use crate::gl_helper::Texture;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_VERSION: &str = "atlas 1";
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Texture(TextureError),
+    Io(std::io::Error),
+    DuplicateName(String),
+    InvalidName(String), // empty or with line breaks, which the manifest can't hold
+    EmptyImage(String),
+    TooLarge {
+        name: String,
+        width: u32,
+        height: u32,
+    },
+    Manifest {
+        line: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtlasError::Texture(err) => write!(f, "{}", err),
+            AtlasError::Io(err) => write!(f, "atlas io error: {}", err),
+            AtlasError::DuplicateName(name) => write!(f, "\"{}\" is already in the atlas", name),
+            AtlasError::InvalidName(name) => write!(f, "{:?} is not a valid region name", name),
+            AtlasError::EmptyImage(name) => write!(f, "\"{}\" has no pixels", name),
+            AtlasError::TooLarge {
+                name,
+                width,
+                height,
+            } => write!(f, "\"{}\" doesn't fit in a {}x{} page", name, width, height),
+            AtlasError::Manifest { line, reason } => {
+                write!(f, "atlas manifest line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+impl From<TextureError> for AtlasError {
+    fn from(err: TextureError) -> Self {
+        AtlasError::Texture(err)
+    }
+}
+
+impl From<image::ImageError> for AtlasError {
+    fn from(err: image::ImageError) -> Self {
+        AtlasError::Texture(TextureError::Image(err))
+    }
+}
+
+impl From<std::io::Error> for AtlasError {
+    fn from(err: std::io::Error) -> Self {
+        AtlasError::Io(err)
+    }
+}
+
+/// Normalized texture coordinates of a region, what `geo::Rect::texture_coords_from` takes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl UvRect {
+    pub const FULL: UvRect = UvRect {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+}
+
+/// Where an image landed, in pixels of its page. Padding and bleed are outside the rect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtlasRegion {
+    pub name: String,
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasPage {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The packed regions without any pixels, written and read as a text manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AtlasLayout {
+    pub pages: Vec<AtlasPage>,
+    pub regions: Vec<AtlasRegion>,
+}
+
+impl AtlasLayout {
+    pub fn get(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    pub fn uv_rect(&self, name: &str) -> Option<UvRect> {
+        let region = self.get(name)?;
+        let page = self.pages[region.page];
+        Some(UvRect {
+            x: region.x as f32 / page.width as f32,
+            y: region.y as f32 / page.height as f32,
+            width: region.width as f32 / page.width as f32,
+            height: region.height as f32 / page.height as f32,
+        })
+    }
+
+    // `page_files` are written next to the page sizes, one per page
+    pub fn to_manifest(&self, page_files: &[String]) -> String {
+        let mut manifest = format!("{}\n", MANIFEST_VERSION);
+        for (page, file) in self.pages.iter().zip(page_files.iter()) {
+            manifest += &format!("page {} {} {}\n", page.width, page.height, file);
+        }
+        for region in &self.regions {
+            manifest += &format!(
+                "region {} {} {} {} {} {}\n",
+                region.page, region.x, region.y, region.width, region.height, region.name
+            );
+        }
+        manifest
+    }
+
+    // the layout and the page file names
+    pub fn from_manifest(manifest: &str) -> Result<(Self, Vec<String>), AtlasError> {
+        let error = |line: usize, reason: &str| AtlasError::Manifest {
+            line: line + 1,
+            reason: reason.to_string(),
+        };
+
+        let mut lines = manifest.lines().enumerate();
+        match lines.next() {
+            Some((_, MANIFEST_VERSION)) => {}
+            _ => return Err(error(0, "not an atlas manifest")),
+        }
+
+        let mut layout = AtlasLayout::default();
+        let mut page_files = Vec::new();
+        for (index, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            // names and files are the rest of the line, they can have spaces
+            let (kind, numbers, count) = if let Some(rest) = line.strip_prefix("page ") {
+                ("page", rest, 2)
+            } else if let Some(rest) = line.strip_prefix("region ") {
+                ("region", rest, 5)
+            } else {
+                return Err(error(index, "expected page or region"));
+            };
+
+            let fields: Vec<&str> = numbers.splitn(count + 1, ' ').collect();
+            if fields.len() != count + 1 || fields[count].is_empty() {
+                return Err(error(index, "missing fields"));
+            }
+            let mut values = Vec::with_capacity(count);
+            for field in &fields[..count] {
+                values.push(
+                    field
+                        .parse::<u32>()
+                        .map_err(|_| error(index, "expected a number"))?,
+                );
+            }
+
+            if kind == "page" {
+                layout.pages.push(AtlasPage {
+                    width: values[0],
+                    height: values[1],
+                });
+                page_files.push(fields[count].to_string());
+            } else {
+                let region = AtlasRegion {
+                    name: fields[count].to_string(),
+                    page: values[0] as usize,
+                    x: values[1],
+                    y: values[2],
+                    width: values[3],
+                    height: values[4],
+                };
+                let page = layout
+                    .pages
+                    .get(region.page)
+                    .ok_or_else(|| error(index, "region on a missing page"))?;
+                let right = region.x.checked_add(region.width);
+                let bottom = region.y.checked_add(region.height);
+                match (right, bottom) {
+                    (Some(right), Some(bottom)) if right <= page.width && bottom <= page.height => {
+                    }
+                    _ => return Err(error(index, "region outside of its page")),
+                }
+                layout.regions.push(region);
+            }
+        }
+        Ok((layout, page_files))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackingAlgorithm {
+    Shelf,    // rows of images, fast and good for images of similar height
+    MaxRects, // best short side fit, tighter for mixed sizes
+}
+
+/// Packs images in to one or more pages.
+///
+/// ```ignore
+/// let atlas = glh::AtlasBuilder::new()
+///     .padding(2)
+///     .add("ship", ship_image)?
+///     .add_path("assets/bullet.png")?
+///     .build(gl, glh::TextureSettings::default())?;
+/// let mut rect = Rect::new(0.0, 0.0, 64.0, 64.0);
+/// rect.texture_coords_from(atlas.uv_rect("ship").unwrap());
+/// ```
+pub struct AtlasBuilder {
+    page_width: u32,
+    page_height: u32,
+    padding: u32,
+    bleed: u32,
+    algorithm: PackingAlgorithm,
+    images: Vec<(String, image::RgbaImage)>,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self {
+            page_width: 2048,
+            page_height: 2048,
+            padding: 2,
+            bleed: 1,
+            algorithm: PackingAlgorithm::MaxRects,
+            images: Vec::new(),
+        }
+    }
+
+    // largest page, pages are shrunk to the next power of two that holds their regions
+    pub fn page_size(mut self, width: u32, height: u32) -> Self {
+        self.page_width = width;
+        self.page_height = height;
+        self
+    }
+
+    // empty pixels between regions
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    // edge pixels repeated around each region, so filtering and mipmaps don't pick up
+    // the neighbours. taken from the padding, so it is at most half of it
+    pub fn bleed(mut self, bleed: u32) -> Self {
+        self.bleed = bleed;
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: PackingAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn add(mut self, name: &str, image: image::RgbaImage) -> Result<Self, AtlasError> {
+        if name.is_empty() || name.contains(['\n', '\r']) {
+            return Err(AtlasError::InvalidName(name.to_string()));
+        }
+        if image.width() == 0 || image.height() == 0 {
+            return Err(AtlasError::EmptyImage(name.to_string()));
+        }
+        if self.images.iter().any(|(existing, _)| existing == name) {
+            return Err(AtlasError::DuplicateName(name.to_string()));
+        }
+        self.images.push((name.to_string(), image));
+        Ok(self)
+    }
+
+    // named after the file stem
+    pub fn add_path<P: AsRef<Path>>(self, path: P) -> Result<Self, AtlasError> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let image = image::open(path)?.to_rgba8();
+        self.add(&name, image)
+    }
+
+    fn bleed_size(&self) -> u32 {
+        self.bleed.min(self.padding / 2)
+    }
+
+    pub fn pack(&self) -> Result<AtlasLayout, AtlasError> {
+        let bleed = self.bleed_size();
+
+        // the largest first packs tighter, the regions keep the order they were added in
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&index| {
+            let image = &self.images[index].1;
+            std::cmp::Reverse((
+                image.width().max(image.height()),
+                image.width() * image.height(),
+            ))
+        });
+
+        // the padding after the last region of a row or column can fall off the page
+        let bin_width = self.page_width + self.padding - 2 * bleed;
+        let bin_height = self.page_height + self.padding - 2 * bleed;
+
+        let mut packers: Vec<Packer> = Vec::new();
+        let mut placed = vec![None; self.images.len()];
+        for index in order {
+            let (name, image) = &self.images[index];
+            let cell_width = image.width() + self.padding;
+            let cell_height = image.height() + self.padding;
+            if cell_width > bin_width || cell_height > bin_height {
+                return Err(AtlasError::TooLarge {
+                    name: name.clone(),
+                    width: self.page_width,
+                    height: self.page_height,
+                });
+            }
+
+            let mut spot = None;
+            for (page, packer) in packers.iter_mut().enumerate() {
+                if let Some((x, y)) = packer.insert(cell_width, cell_height) {
+                    spot = Some((page, x, y));
+                    break;
+                }
+            }
+            let (page, x, y) = match spot {
+                Some(spot) => spot,
+                None => {
+                    let mut packer = Packer::new(self.algorithm, bin_width, bin_height);
+                    let (x, y) = packer
+                        .insert(cell_width, cell_height)
+                        .expect("an empty page fits the region");
+                    packers.push(packer);
+                    (packers.len() - 1, x, y)
+                }
+            };
+
+            placed[index] = Some(AtlasRegion {
+                name: name.clone(),
+                page,
+                x: x + bleed,
+                y: y + bleed,
+                width: image.width(),
+                height: image.height(),
+            });
+        }
+
+        let regions: Vec<AtlasRegion> = placed.into_iter().flatten().collect();
+        let mut pages = vec![
+            AtlasPage {
+                width: 1,
+                height: 1
+            };
+            packers.len()
+        ];
+        for region in &regions {
+            let page = &mut pages[region.page];
+            page.width = page.width.max(region.x + region.width + bleed);
+            page.height = page.height.max(region.y + region.height + bleed);
+        }
+        for page in &mut pages {
+            page.width = page.width.next_power_of_two().min(self.page_width);
+            page.height = page.height.next_power_of_two().min(self.page_height);
+        }
+
+        Ok(AtlasLayout { pages, regions })
+    }
+
+    // packs and copies the images in to the pages, without a GL context
+    pub fn build_images(&self) -> Result<(AtlasLayout, Vec<image::RgbaImage>), AtlasError> {
+        let layout = self.pack()?;
+        let mut pages: Vec<image::RgbaImage> = layout
+            .pages
+            .iter()
+            .map(|page| image::RgbaImage::new(page.width, page.height))
+            .collect();
+
+        let bleed = self.bleed_size();
+        for ((_, image), region) in self.images.iter().zip(layout.regions.iter()) {
+            blit_with_bleed(&mut pages[region.page], image, region.x, region.y, bleed);
+        }
+        Ok((layout, pages))
+    }
+
+    pub fn build(
+        &self,
+        gl: &glow::Context,
+        settings: TextureSettings,
+    ) -> Result<TextureAtlas, AtlasError> {
+        let (layout, images) = self.build_images()?;
+        TextureAtlas::from_images(gl, layout, &images, settings)
+    }
+
+    // writes `name`.atlas and a png per page to `dir`, to load later with TextureAtlas::load
+    pub fn bake<P: AsRef<Path>>(&self, dir: P, name: &str) -> Result<AtlasLayout, AtlasError> {
+        let dir = dir.as_ref();
+        let (layout, images) = self.build_images()?;
+
+        let mut page_files = Vec::with_capacity(images.len());
+        for (index, image) in images.iter().enumerate() {
+            let file = format!("{}_{}.png", name, index);
+            image.save(dir.join(&file))?;
+            page_files.push(file);
+        }
+        std::fs::write(
+            dir.join(format!("{}.atlas", name)),
+            layout.to_manifest(&page_files),
+        )?;
+        Ok(layout)
+    }
+}
+
+// copies the image and repeats its edge pixels `bleed` times around it
+fn blit_with_bleed(
+    page: &mut image::RgbaImage,
+    image: &image::RgbaImage,
+    x: u32,
+    y: u32,
+    bleed: u32,
+) {
+    let (width, height) = image.dimensions();
+    let left = x as i64 - bleed as i64;
+    let top = y as i64 - bleed as i64;
+    for page_y in top..(y + height + bleed) as i64 {
+        for page_x in left..(x + width + bleed) as i64 {
+            if page_x < 0
+                || page_y < 0
+                || page_x >= page.width() as i64
+                || page_y >= page.height() as i64
+            {
+                continue;
+            }
+            let source_x = (page_x - x as i64).clamp(0, width as i64 - 1) as u32;
+            let source_y = (page_y - y as i64).clamp(0, height as i64 - 1) as u32;
+            page.put_pixel(
+                page_x as u32,
+                page_y as u32,
+                *image.get_pixel(source_x, source_y),
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PackRect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl PackRect {
+    fn contains(&self, other: &PackRect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+
+    fn intersects(&self, other: &PackRect) -> bool {
+        other.x < self.x + self.width
+            && other.x + other.width > self.x
+            && other.y < self.y + self.height
+            && other.y + other.height > self.y
+    }
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    cursor: u32,
+}
+
+enum Packer {
+    Shelf {
+        width: u32,
+        height: u32,
+        shelves: Vec<Shelf>,
+    },
+    MaxRects {
+        free: Vec<PackRect>,
+    },
+}
+
+impl Packer {
+    fn new(algorithm: PackingAlgorithm, width: u32, height: u32) -> Self {
+        match algorithm {
+            PackingAlgorithm::Shelf => Packer::Shelf {
+                width,
+                height,
+                shelves: Vec::new(),
+            },
+            PackingAlgorithm::MaxRects => Packer::MaxRects {
+                free: vec![PackRect {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                }],
+            },
+        }
+    }
+
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        match self {
+            Packer::Shelf {
+                width: bin_width,
+                height: bin_height,
+                shelves,
+            } => insert_shelf(*bin_width, *bin_height, shelves, width, height),
+            Packer::MaxRects { free } => insert_max_rects(free, width, height),
+        }
+    }
+}
+
+// the shelf wasting the least height, or a new shelf under the last one
+fn insert_shelf(
+    bin_width: u32,
+    bin_height: u32,
+    shelves: &mut Vec<Shelf>,
+    width: u32,
+    height: u32,
+) -> Option<(u32, u32)> {
+    let best = shelves
+        .iter_mut()
+        .filter(|shelf| height <= shelf.height && shelf.cursor + width <= bin_width)
+        .min_by_key(|shelf| shelf.height - height);
+    if let Some(shelf) = best {
+        let spot = (shelf.cursor, shelf.y);
+        shelf.cursor += width;
+        return Some(spot);
+    }
+
+    let y = shelves
+        .last()
+        .map(|shelf| shelf.y + shelf.height)
+        .unwrap_or(0);
+    if y + height > bin_height || width > bin_width {
+        return None;
+    }
+    shelves.push(Shelf {
+        y,
+        height,
+        cursor: width,
+    });
+    Some((0, y))
+}
+
+fn insert_max_rects(free: &mut Vec<PackRect>, width: u32, height: u32) -> Option<(u32, u32)> {
+    let best = free
+        .iter()
+        .filter(|rect| rect.width >= width && rect.height >= height)
+        .min_by_key(|rect| {
+            let leftover_x = rect.width - width;
+            let leftover_y = rect.height - height;
+            (leftover_x.min(leftover_y), leftover_x.max(leftover_y))
+        })?;
+    let placed = PackRect {
+        x: best.x,
+        y: best.y,
+        width,
+        height,
+    };
+
+    // every free rect overlapping the placed one is split in to the parts around it
+    let mut split = Vec::new();
+    free.retain(|rect| {
+        if !rect.intersects(&placed) {
+            return true;
+        }
+        if placed.x > rect.x {
+            split.push(PackRect {
+                width: placed.x - rect.x,
+                ..*rect
+            });
+        }
+        if placed.x + placed.width < rect.x + rect.width {
+            split.push(PackRect {
+                x: placed.x + placed.width,
+                width: rect.x + rect.width - (placed.x + placed.width),
+                ..*rect
+            });
+        }
+        if placed.y > rect.y {
+            split.push(PackRect {
+                height: placed.y - rect.y,
+                ..*rect
+            });
+        }
+        if placed.y + placed.height < rect.y + rect.height {
+            split.push(PackRect {
+                y: placed.y + placed.height,
+                height: rect.y + rect.height - (placed.y + placed.height),
+                ..*rect
+            });
+        }
+        false
+    });
+    free.extend(split);
+
+    // drop free rects inside other free rects
+    let mut index = 0;
+    while index < free.len() {
+        let rect = free[index];
+        let contained = free.iter().enumerate().any(|(other, outer)| {
+            other != index && outer.contains(&rect) && (outer != &rect || other < index)
+        });
+        if contained {
+            free.swap_remove(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    Some((placed.x, placed.y))
+}
+
+/// Pages uploaded as textures plus the layout to find the regions in them.
+pub struct TextureAtlas {
+    pub layout: AtlasLayout,
+    pub pages: Vec<Texture>,
+}
+
+impl TextureAtlas {
+    pub fn from_images(
+        gl: &glow::Context,
+        layout: AtlasLayout,
+        images: &[image::RgbaImage],
+        settings: TextureSettings,
+    ) -> Result<Self, AtlasError> {
+        let pages = images
+            .iter()
+            .map(|image| Texture::new_from_image_rgbau8(gl, image, settings))
+            .collect();
+        Ok(Self { layout, pages })
+    }
+
+    // a manifest written by AtlasBuilder::bake, page files are relative to it
+    pub fn load<P: AsRef<Path>>(
+        gl: &glow::Context,
+        manifest_path: P,
+        settings: TextureSettings,
+    ) -> Result<Self, AtlasError> {
+        let manifest_path = manifest_path.as_ref();
+        let (layout, page_files) =
+            AtlasLayout::from_manifest(&std::fs::read_to_string(manifest_path)?)?;
+
+        let dir = manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let mut images = Vec::with_capacity(page_files.len());
+        for file in &page_files {
+            let path: PathBuf = dir.join(file);
+            images.push(image::open(path)?.to_rgba8());
+        }
+        Self::from_images(gl, layout, &images, settings)
+    }
+
+    pub fn uv_rect(&self, name: &str) -> Option<UvRect> {
+        self.layout.uv_rect(name)
+    }
+
+    pub fn get_page(&self, name: &str) -> Option<&Texture> {
+        self.layout
+            .get(name)
+            .and_then(|region| self.pages.get(region.page))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> image::RgbaImage {
+        image::RgbaImage::from_pixel(width, height, image::Rgba([value, value, value, 255]))
+    }
+
+    fn builder(algorithm: PackingAlgorithm) -> AtlasBuilder {
+        let mut builder = AtlasBuilder::new()
+            .page_size(128, 128)
+            .padding(2)
+            .algorithm(algorithm);
+        for (index, (width, height)) in [(40, 30), (64, 20), (10, 60), (33, 33), (50, 50), (8, 8)]
+            .iter()
+            .enumerate()
+        {
+            builder = builder
+                .add(
+                    &format!("image {}", index),
+                    solid(*width, *height, index as u8),
+                )
+                .unwrap();
+        }
+        builder
+    }
+
+    #[test]
+    fn regions_dont_overlap() {
+        for algorithm in [PackingAlgorithm::Shelf, PackingAlgorithm::MaxRects] {
+            let layout = builder(algorithm).pack().unwrap();
+            assert_eq!(layout.regions.len(), 6);
+            assert_eq!(layout.regions[0].name, "image 0");
+
+            for (index, region) in layout.regions.iter().enumerate() {
+                let page = layout.pages[region.page];
+                assert!(region.x + region.width <= page.width);
+                assert!(region.y + region.height <= page.height);
+
+                // regions grown by the padding never touch
+                for other in &layout.regions[index + 1..] {
+                    let a = PackRect {
+                        x: region.x,
+                        y: region.y,
+                        width: region.width + 2,
+                        height: region.height + 2,
+                    };
+                    let b = PackRect {
+                        x: other.x,
+                        y: other.y,
+                        width: other.width,
+                        height: other.height,
+                    };
+                    let c = PackRect {
+                        x: other.x,
+                        y: other.y,
+                        width: other.width + 2,
+                        height: other.height + 2,
+                    };
+                    let d = PackRect {
+                        x: region.x,
+                        y: region.y,
+                        width: region.width,
+                        height: region.height,
+                    };
+                    assert!(
+                        region.page != other.page || (!a.intersects(&b) && !c.intersects(&d)),
+                        "{:?} and {:?} overlap",
+                        region,
+                        other
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn spills_to_more_pages() {
+        let layout = AtlasBuilder::new()
+            .page_size(64, 64)
+            .add("a", solid(60, 60, 0))
+            .unwrap()
+            .add("b", solid(60, 60, 0))
+            .unwrap()
+            .pack()
+            .unwrap();
+        assert_eq!(layout.pages.len(), 2);
+        assert_eq!(layout.regions[1].page, 1);
+
+        let too_large = AtlasBuilder::new()
+            .page_size(64, 64)
+            .add("a", solid(100, 10, 0))
+            .unwrap()
+            .pack();
+        assert!(matches!(too_large, Err(AtlasError::TooLarge { .. })));
+        assert!(AtlasBuilder::new()
+            .add("a", solid(1, 1, 0))
+            .unwrap()
+            .add("a", solid(1, 1, 0))
+            .is_err());
+        assert!(matches!(
+            AtlasBuilder::new().add("a\nb", solid(1, 1, 0)),
+            Err(AtlasError::InvalidName(_))
+        ));
+        assert!(matches!(
+            AtlasBuilder::new().add("a", solid(0, 4, 0)),
+            Err(AtlasError::EmptyImage(_))
+        ));
+    }
+
+    #[test]
+    fn bleed_repeats_the_edges() {
+        let (layout, pages) = AtlasBuilder::new()
+            .padding(4)
+            .bleed(2)
+            .add("a", solid(4, 4, 200))
+            .unwrap()
+            .build_images()
+            .unwrap();
+        let region = &layout.regions[0];
+        assert_eq!((region.x, region.y), (2, 2));
+        assert_eq!(pages[0].get_pixel(0, 0).0, [200, 200, 200, 255]);
+        assert_eq!(pages[0].get_pixel(7, 7).0, [200, 200, 200, 255]);
+        assert_eq!(pages[0].dimensions(), (8, 8));
+
+        let uv = layout.uv_rect("a").unwrap();
+        assert_eq!(uv.x, 2.0 / pages[0].width() as f32);
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let layout = builder(PackingAlgorithm::MaxRects).pack().unwrap();
+        let files: Vec<String> = (0..layout.pages.len())
+            .map(|index| format!("sprites {}.png", index))
+            .collect();
+
+        let (parsed, parsed_files) =
+            AtlasLayout::from_manifest(&layout.to_manifest(&files)).unwrap();
+        assert_eq!(parsed, layout);
+        assert_eq!(parsed_files, files);
+
+        assert!(AtlasLayout::from_manifest("atlas 1\nregion 0 0 0 1 1 a\n").is_err());
+        assert!(AtlasLayout::from_manifest("not an atlas\n").is_err());
+        // x + width wraps around u32
+        let overflow = "atlas 1\npage 8 8 a.png\nregion 0 4294967295 0 2 2 a\n";
+        assert!(matches!(
+            AtlasLayout::from_manifest(overflow),
+            Err(AtlasError::Manifest { .. })
+        ));
+    }
+}
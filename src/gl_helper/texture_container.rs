@@ -0,0 +1,422 @@
+use crate::gl_helper::compressed_texture::{CompressedFormat, CompressedImage};
+use crate::gl_helper::texture_error::TextureError;
+use std::convert::TryFrom;
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+];
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+
+// KTX2 or DDS, picked from the magic bytes
+pub fn parse(bytes: &[u8]) -> Result<CompressedImage, TextureError> {
+    if bytes.starts_with(&KTX2_IDENTIFIER) {
+        parse_ktx2(bytes)
+    } else if bytes.starts_with(DDS_MAGIC) {
+        parse_dds(bytes)
+    } else {
+        Err(TextureError::InvalidContainer(
+            "not a KTX2 or DDS file".to_string(),
+        ))
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, TextureError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(truncated)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, TextureError> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
+
+// a full mip chain down to 1x1 at most, so a corrupt count can't run past the image
+fn check_level_count(level_count: u32, width: u32, height: u32) -> Result<(), TextureError> {
+    let max_levels = 32 - width.max(height).leading_zeros();
+    if level_count > max_levels {
+        return Err(TextureError::InvalidContainer(format!(
+            "{} mip levels for a {}x{} image",
+            level_count, width, height
+        )));
+    }
+    Ok(())
+}
+
+// the byte range of a level, None if it would overflow
+fn level_range(offset: usize, size: usize) -> Option<std::ops::Range<usize>> {
+    Some(offset..offset.checked_add(size)?)
+}
+
+fn truncated() -> TextureError {
+    TextureError::InvalidContainer("file is truncated".to_string())
+}
+
+fn unsupported(what: String) -> TextureError {
+    TextureError::UnsupportedFormat(what)
+}
+
+// only 2D images, KTX2 cube maps, arrays and volumes are rejected
+pub fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage, TextureError> {
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(TextureError::InvalidContainer(
+            "missing KTX2 identifier".to_string(),
+        ));
+    }
+
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let depth = read_u32(bytes, 28)?;
+    let layers = read_u32(bytes, 32)?;
+    let faces = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?.max(1);
+    let supercompression = read_u32(bytes, 44)?;
+
+    if supercompression != 0 {
+        return Err(unsupported(format!(
+            "KTX2 supercompression scheme {} ( BasisLZ, zstd, zlib )",
+            supercompression
+        )));
+    }
+    if depth > 1 || layers > 1 || faces > 1 || height == 0 {
+        return Err(unsupported(format!(
+            "KTX2 with depth {}, {} layers and {} faces, only 2D textures load",
+            depth, layers, faces
+        )));
+    }
+
+    check_level_count(level_count, width, height)?;
+
+    let (format, srgb) = vk_format_to_compressed(vk_format)
+        .ok_or_else(|| unsupported(format!("KTX2 vkFormat {}", vk_format)))?;
+
+    // the level index follows the 48 byte header and 32 bytes of data format / key value offsets
+    let mut levels = Vec::new();
+    for level in 0..level_count {
+        let entry = 80 + level as usize * 24;
+        // past usize on wasm32 means past the end of the file
+        let offset = usize::try_from(read_u64(bytes, entry)?).map_err(|_| truncated())?;
+        let length = usize::try_from(read_u64(bytes, entry + 8)?).map_err(|_| truncated())?;
+
+        let expected = format.level_size((width >> level).max(1), (height >> level).max(1));
+        if length < expected {
+            return Err(TextureError::InvalidContainer(format!(
+                "level {} is {} bytes, expected {}",
+                level, length, expected
+            )));
+        }
+        let data = level_range(offset, expected)
+            .and_then(|range| bytes.get(range))
+            .ok_or_else(truncated)?;
+        levels.push(data.to_vec());
+    }
+
+    Ok(CompressedImage {
+        format,
+        srgb,
+        width,
+        height,
+        levels,
+    })
+}
+
+// block compressed VkFormat values and whether they are sRGB
+fn vk_format_to_compressed(vk_format: u32) -> Option<(CompressedFormat, bool)> {
+    let format = match vk_format {
+        131 | 132 => CompressedFormat::Bc1 { alpha: false },
+        133 | 134 => CompressedFormat::Bc1 { alpha: true },
+        135 | 136 => CompressedFormat::Bc2,
+        137 | 138 => CompressedFormat::Bc3,
+        139 => CompressedFormat::Bc4 { signed: false },
+        140 => CompressedFormat::Bc4 { signed: true },
+        141 => CompressedFormat::Bc5 { signed: false },
+        142 => CompressedFormat::Bc5 { signed: true },
+        143 => CompressedFormat::Bc6h { signed: false },
+        144 => CompressedFormat::Bc6h { signed: true },
+        145 | 146 => CompressedFormat::Bc7,
+        147 | 148 => CompressedFormat::Etc2Rgb8,
+        149 | 150 => CompressedFormat::Etc2Rgb8A1,
+        151 | 152 => CompressedFormat::Etc2Rgba8,
+        153 => CompressedFormat::EacR11 { signed: false },
+        154 => CompressedFormat::EacR11 { signed: true },
+        155 => CompressedFormat::EacRg11 { signed: false },
+        156 => CompressedFormat::EacRg11 { signed: true },
+        157..=184 => {
+            const ASTC_BLOCKS: [(u32, u32); 14] = [
+                (4, 4),
+                (5, 4),
+                (5, 5),
+                (6, 5),
+                (6, 6),
+                (8, 5),
+                (8, 6),
+                (8, 8),
+                (10, 5),
+                (10, 6),
+                (10, 8),
+                (10, 10),
+                (12, 10),
+                (12, 12),
+            ];
+            let (block_width, block_height) = ASTC_BLOCKS[(vk_format - 157) as usize / 2];
+            CompressedFormat::Astc {
+                block_width,
+                block_height,
+            }
+        }
+        _ => return None,
+    };
+
+    // unorm / srgb pairs alternate, the srgb one second
+    let srgb = match vk_format {
+        131..=138 | 145..=152 | 157..=184 => vk_format % 2 == 0,
+        _ => false,
+    };
+    Some((format, srgb))
+}
+
+const DDS_HEADER_SIZE: u32 = 124;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const DDS_DIMENSION_TEXTURE2D: u32 = 3;
+
+// legacy FourCC ( DXT1 - DXT5, ATI1, ATI2 ) and DX10 headers, 2D only
+pub fn parse_dds(bytes: &[u8]) -> Result<CompressedImage, TextureError> {
+    if !bytes.starts_with(DDS_MAGIC) {
+        return Err(TextureError::InvalidContainer(
+            "missing DDS magic".to_string(),
+        ));
+    }
+    if read_u32(bytes, 4)? != DDS_HEADER_SIZE {
+        return Err(TextureError::InvalidContainer(
+            "bad DDS header size".to_string(),
+        ));
+    }
+
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let level_count = read_u32(bytes, 28)?.max(1);
+    let pixel_format_flags = read_u32(bytes, 80)?;
+    let four_cc = bytes.get(84..88).ok_or_else(truncated)?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    check_level_count(level_count, width, height)?;
+    if caps2 & (DDSCAPS2_CUBEMAP | DDSCAPS2_VOLUME) != 0 {
+        return Err(unsupported(
+            "DDS cube maps and volumes, only 2D textures load".to_string(),
+        ));
+    }
+    if pixel_format_flags & DDPF_FOURCC == 0 {
+        return Err(unsupported(
+            "uncompressed DDS, only block compressed data loads".to_string(),
+        ));
+    }
+
+    let mut data_offset = 128;
+    let (format, srgb) = if four_cc == b"DX10" {
+        let dxgi_format = read_u32(bytes, 128)?;
+        let dimension = read_u32(bytes, 132)?;
+        let misc_flag = read_u32(bytes, 136)?;
+        let array_size = read_u32(bytes, 140)?;
+        data_offset += 20;
+
+        if dimension != DDS_DIMENSION_TEXTURE2D
+            || misc_flag & DDS_RESOURCE_MISC_TEXTURECUBE != 0
+            || array_size > 1
+        {
+            return Err(unsupported(
+                "DDS cube maps, arrays and volumes, only 2D textures load".to_string(),
+            ));
+        }
+        dxgi_format_to_compressed(dxgi_format)
+            .ok_or_else(|| unsupported(format!("DXGI format {}", dxgi_format)))?
+    } else {
+        let format = match four_cc {
+            b"DXT1" => CompressedFormat::Bc1 { alpha: true },
+            b"DXT2" | b"DXT3" => CompressedFormat::Bc2,
+            b"DXT4" | b"DXT5" => CompressedFormat::Bc3,
+            b"ATI1" | b"BC4U" => CompressedFormat::Bc4 { signed: false },
+            b"BC4S" => CompressedFormat::Bc4 { signed: true },
+            b"ATI2" | b"BC5U" => CompressedFormat::Bc5 { signed: false },
+            b"BC5S" => CompressedFormat::Bc5 { signed: true },
+            _ => {
+                return Err(unsupported(format!(
+                    "DDS FourCC {}",
+                    String::from_utf8_lossy(four_cc)
+                )))
+            }
+        };
+        (format, false)
+    };
+
+    // levels are stored one after the other, largest first
+    let mut levels = Vec::new();
+    for level in 0..level_count {
+        let size = format.level_size((width >> level).max(1), (height >> level).max(1));
+        let data = level_range(data_offset, size)
+            .and_then(|range| bytes.get(range))
+            .ok_or_else(truncated)?;
+        levels.push(data.to_vec());
+        data_offset += size;
+    }
+
+    Ok(CompressedImage {
+        format,
+        srgb,
+        width,
+        height,
+        levels,
+    })
+}
+
+fn dxgi_format_to_compressed(dxgi_format: u32) -> Option<(CompressedFormat, bool)> {
+    let format = match dxgi_format {
+        71 => (CompressedFormat::Bc1 { alpha: true }, false),
+        72 => (CompressedFormat::Bc1 { alpha: true }, true),
+        74 => (CompressedFormat::Bc2, false),
+        75 => (CompressedFormat::Bc2, true),
+        77 => (CompressedFormat::Bc3, false),
+        78 => (CompressedFormat::Bc3, true),
+        80 => (CompressedFormat::Bc4 { signed: false }, false),
+        81 => (CompressedFormat::Bc4 { signed: true }, false),
+        83 => (CompressedFormat::Bc5 { signed: false }, false),
+        84 => (CompressedFormat::Bc5 { signed: true }, false),
+        95 => (CompressedFormat::Bc6h { signed: false }, false),
+        96 => (CompressedFormat::Bc6h { signed: true }, false),
+        98 => (CompressedFormat::Bc7, false),
+        99 => (CompressedFormat::Bc7, true),
+        _ => return None,
+    };
+    Some(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ktx2(vk_format: u32, width: u32, height: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+        for value in [vk_format, 1, width, height, 0, 0, 1, levels.len() as u32, 0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(80, 0);
+
+        let mut offset = 80 + levels.len() * 24;
+        for level in levels {
+            for value in [offset as u64, level.len() as u64, level.len() as u64] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            offset += level.len();
+        }
+        for level in levels {
+            bytes.extend_from_slice(level);
+        }
+        bytes
+    }
+
+    fn dds(four_cc: &[u8; 4], width: u32, height: u32, levels: u32, dxgi: Option<u32>) -> Vec<u8> {
+        let mut bytes = vec![0u8; 128];
+        bytes[0..4].copy_from_slice(DDS_MAGIC);
+        bytes[4..8].copy_from_slice(&DDS_HEADER_SIZE.to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[28..32].copy_from_slice(&levels.to_le_bytes());
+        bytes[80..84].copy_from_slice(&DDPF_FOURCC.to_le_bytes());
+        bytes[84..88].copy_from_slice(four_cc);
+        if let Some(dxgi) = dxgi {
+            for value in [dxgi, DDS_DIMENSION_TEXTURE2D, 0, 1, 0] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn ktx2_levels() {
+        let levels = vec![vec![1u8; 4 * 16], vec![2u8; 16], vec![3u8; 16]];
+        let image = parse(&ktx2(146, 8, 8, &levels)).unwrap();
+        assert_eq!(image.format, CompressedFormat::Bc7);
+        assert!(image.srgb);
+        assert_eq!((image.width, image.height), (8, 8));
+        assert_eq!(image.levels, levels);
+
+        let astc = parse(&ktx2(163, 6, 5, &[vec![0u8; 16]])).unwrap();
+        assert_eq!(
+            astc.format,
+            CompressedFormat::Astc {
+                block_width: 6,
+                block_height: 5
+            }
+        );
+        assert!(!astc.srgb);
+    }
+
+    #[test]
+    fn ktx2_errors() {
+        let mut cube = ktx2(145, 4, 4, &[vec![0u8; 16]]);
+        cube[36..40].copy_from_slice(&6u32.to_le_bytes());
+        assert!(matches!(
+            parse(&cube),
+            Err(TextureError::UnsupportedFormat(_))
+        ));
+
+        let short = ktx2(145, 8, 8, &[vec![0u8; 16]]);
+        assert!(matches!(
+            parse(&short),
+            Err(TextureError::InvalidContainer(_))
+        ));
+
+        assert!(matches!(
+            parse(&ktx2(37, 4, 4, &[vec![0u8; 64]])),
+            Err(TextureError::UnsupportedFormat(_))
+        ));
+
+        // a 4x4 image has 3 levels at most
+        let mut corrupt = ktx2(145, 4, 4, &[vec![0u8; 16]]);
+        corrupt[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            parse(&corrupt),
+            Err(TextureError::InvalidContainer(_))
+        ));
+
+        // a level offset at the end of the address space
+        let mut overflow = ktx2(145, 4, 4, &[vec![0u8; 16]]);
+        overflow[80..88].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            parse(&overflow),
+            Err(TextureError::InvalidContainer(_))
+        ));
+    }
+
+    #[test]
+    fn dds_legacy_and_dx10() {
+        let mut bytes = dds(b"DXT5", 8, 4, 2, None);
+        bytes.extend(vec![7u8; 2 * 16]);
+        bytes.extend(vec![9u8; 16]);
+        let image = parse(&bytes).unwrap();
+        assert_eq!(image.format, CompressedFormat::Bc3);
+        assert_eq!(image.levels, vec![vec![7u8; 32], vec![9u8; 16]]);
+
+        let mut bytes = dds(b"DX10", 4, 4, 1, Some(72));
+        bytes.extend(vec![0u8; 8]);
+        let image = parse(&bytes).unwrap();
+        assert_eq!(image.format, CompressedFormat::Bc1 { alpha: true });
+        assert!(image.srgb);
+
+        let corrupt = dds(b"DXT1", 4, 4, u32::MAX, None);
+        assert!(matches!(
+            parse(&corrupt),
+            Err(TextureError::InvalidContainer(_))
+        ));
+
+        let truncated = dds(b"DXT1", 4, 4, 1, None);
+        assert!(matches!(
+            parse(&truncated),
+            Err(TextureError::InvalidContainer(_))
+        ));
+        assert!(parse(b"PNG.....").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+use crate::gl_helper::ShaderError;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum TextureError {
+    Image(image::ImageError),
+    UnsupportedFormat(String),
+    InvalidDimensions(String),
+    InvalidContainer(String), // malformed KTX2 or DDS data
+    Shader(ShaderError),      // shaders used to convert the texture on the GPU
+    Aborted(String),          // an AsyncTexture that stopped loading
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Image(err) => write!(f, "Could not load texture: {}", err),
+            TextureError::UnsupportedFormat(format) => {
+                write!(f, "texture format {} is not supported", format)
+            }
+            TextureError::InvalidDimensions(reason) => write!(f, "invalid dimensions, {}", reason),
+            TextureError::InvalidContainer(reason) => {
+                write!(f, "invalid texture container, {}", reason)
+            }
+            TextureError::Aborted(reason) => write!(f, "texture loading aborted, {}", reason),
+            TextureError::Shader(err) => write!(f, "texture conversion shader failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<image::ImageError> for TextureError {
+    fn from(err: image::ImageError) -> Self {
+        TextureError::Image(err)
+    }
+}
+
+impl From<ShaderError> for TextureError {
+    fn from(err: ShaderError) -> Self {
+        TextureError::Shader(err)
+    }
+}
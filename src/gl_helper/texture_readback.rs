@@ -0,0 +1,443 @@
+use crate::gl_helper::texture_error::TextureError;
+use crate::gl_helper::{Bindable, Fbo, Texture};
+use glow::{self, HasContext};
+use image::DynamicImage;
+use std::path::Path;
+
+/// `image` buffer a texture is read back in to, picked from its internal format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadbackLayout {
+    Luma8,
+    LumaA8,
+    Rgb8,
+    Rgba8,
+    Luma16,
+    LumaA16,
+    Rgb16,
+    Rgba16,
+    Rgb32F,
+    Rgba32F,
+}
+
+impl ReadbackLayout {
+    // None for formats without a matching image buffer, ie: depth or integer textures
+    pub fn from_internal_format(internal_format: u32) -> Option<Self> {
+        let layout = match internal_format {
+            glow::R8 => ReadbackLayout::Luma8,
+            glow::RG8 => ReadbackLayout::LumaA8,
+            glow::RGB8 | glow::SRGB8 => ReadbackLayout::Rgb8,
+            glow::RGBA8 | glow::SRGB8_ALPHA8 => ReadbackLayout::Rgba8,
+            glow::R16 => ReadbackLayout::Luma16,
+            glow::RG16 => ReadbackLayout::LumaA16,
+            glow::RGB16 => ReadbackLayout::Rgb16,
+            glow::RGBA16 => ReadbackLayout::Rgba16,
+            // image has no one or two channel float buffers, the missing channels read as 0
+            glow::R16F
+            | glow::R32F
+            | glow::RG16F
+            | glow::RG32F
+            | glow::RGB16F
+            | glow::RGB32F
+            | glow::R11F_G11F_B10F => ReadbackLayout::Rgb32F,
+            glow::RGBA16F | glow::RGBA32F => ReadbackLayout::Rgba32F,
+            _ => return None,
+        };
+        Some(layout)
+    }
+
+    // ( format, data type ) passed to GL when reading the pixels
+    pub fn pack_format(&self) -> (u32, u32) {
+        match self {
+            ReadbackLayout::Luma8 => (glow::RED, glow::UNSIGNED_BYTE),
+            ReadbackLayout::LumaA8 => (glow::RG, glow::UNSIGNED_BYTE),
+            ReadbackLayout::Rgb8 => (glow::RGB, glow::UNSIGNED_BYTE),
+            ReadbackLayout::Rgba8 => (glow::RGBA, glow::UNSIGNED_BYTE),
+            ReadbackLayout::Luma16 => (glow::RED, glow::UNSIGNED_SHORT),
+            ReadbackLayout::LumaA16 => (glow::RG, glow::UNSIGNED_SHORT),
+            ReadbackLayout::Rgb16 => (glow::RGB, glow::UNSIGNED_SHORT),
+            ReadbackLayout::Rgba16 => (glow::RGBA, glow::UNSIGNED_SHORT),
+            ReadbackLayout::Rgb32F => (glow::RGB, glow::FLOAT),
+            ReadbackLayout::Rgba32F => (glow::RGBA, glow::FLOAT),
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            ReadbackLayout::Luma8 | ReadbackLayout::Luma16 => 1,
+            ReadbackLayout::LumaA8 | ReadbackLayout::LumaA16 => 2,
+            ReadbackLayout::Rgb8 | ReadbackLayout::Rgb16 | ReadbackLayout::Rgb32F => 3,
+            ReadbackLayout::Rgba8 | ReadbackLayout::Rgba16 | ReadbackLayout::Rgba32F => 4,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, ReadbackLayout::Rgb32F | ReadbackLayout::Rgba32F)
+    }
+
+    // WebGL2 only reads RGBA / UNSIGNED_BYTE, and RGBA / FLOAT with EXT_color_buffer_float
+    pub fn rgba_pack_format(&self) -> (u32, u32) {
+        if self.is_float() {
+            (glow::RGBA, glow::FLOAT)
+        } else {
+            (glow::RGBA, glow::UNSIGNED_BYTE)
+        }
+    }
+
+    // repacks pixels read with rgba_pack_format in to this layout,
+    // 16 bit layouts are widened from the 8 bits WebGL2 reads
+    pub fn repack_rgba(&self, rgba: &[u8]) -> Vec<u8> {
+        let channels = self.channels();
+        match self {
+            ReadbackLayout::Rgb32F | ReadbackLayout::Rgba32F => rgba
+                .chunks_exact(16)
+                .flat_map(|pixel| pixel[..channels * 4].iter().copied())
+                .collect(),
+            ReadbackLayout::Luma16
+            | ReadbackLayout::LumaA16
+            | ReadbackLayout::Rgb16
+            | ReadbackLayout::Rgba16 => rgba
+                .chunks_exact(4)
+                .flat_map(|pixel| {
+                    pixel[..channels]
+                        .iter()
+                        .flat_map(|c| (*c as u16 * 257).to_ne_bytes())
+                })
+                .collect(),
+            _ => rgba
+                .chunks_exact(4)
+                .flat_map(|pixel| pixel[..channels].iter().copied())
+                .collect(),
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            ReadbackLayout::Luma8 => 1,
+            ReadbackLayout::LumaA8 => 2,
+            ReadbackLayout::Rgb8 => 3,
+            ReadbackLayout::Rgba8 => 4,
+            ReadbackLayout::Luma16 => 2,
+            ReadbackLayout::LumaA16 => 4,
+            ReadbackLayout::Rgb16 => 6,
+            ReadbackLayout::Rgba16 => 8,
+            ReadbackLayout::Rgb32F => 12,
+            ReadbackLayout::Rgba32F => 16,
+        }
+    }
+
+    // wraps tightly packed pixels, as read with pack_format, in the matching image buffer
+    pub fn to_image(&self, width: u32, height: u32, bytes: &[u8]) -> Option<DynamicImage> {
+        use image::ImageBuffer;
+
+        let image = match self {
+            ReadbackLayout::Luma8 => {
+                DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, bytes.to_vec())?)
+            }
+            ReadbackLayout::LumaA8 => {
+                DynamicImage::ImageLumaA8(ImageBuffer::from_raw(width, height, bytes.to_vec())?)
+            }
+            ReadbackLayout::Rgb8 => {
+                DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, bytes.to_vec())?)
+            }
+            ReadbackLayout::Rgba8 => {
+                DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, bytes.to_vec())?)
+            }
+            ReadbackLayout::Luma16 => {
+                DynamicImage::ImageLuma16(ImageBuffer::from_raw(width, height, to_u16(bytes))?)
+            }
+            ReadbackLayout::LumaA16 => {
+                DynamicImage::ImageLumaA16(ImageBuffer::from_raw(width, height, to_u16(bytes))?)
+            }
+            ReadbackLayout::Rgb16 => {
+                DynamicImage::ImageRgb16(ImageBuffer::from_raw(width, height, to_u16(bytes))?)
+            }
+            ReadbackLayout::Rgba16 => {
+                DynamicImage::ImageRgba16(ImageBuffer::from_raw(width, height, to_u16(bytes))?)
+            }
+            ReadbackLayout::Rgb32F => {
+                DynamicImage::ImageRgb32F(ImageBuffer::from_raw(width, height, to_f32(bytes))?)
+            }
+            ReadbackLayout::Rgba32F => {
+                DynamicImage::ImageRgba32F(ImageBuffer::from_raw(width, height, to_f32(bytes))?)
+            }
+        };
+        Some(image)
+    }
+}
+
+fn to_u16(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+        .collect()
+}
+
+fn to_f32(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+impl Texture {
+    // rows come back in GL order, the first row is t = 0. That is the first row of the image
+    // for textures created from images and the bottom row for textures rendered in to
+    pub fn read_to_image(&self, gl: &glow::Context) -> Result<DynamicImage, TextureError> {
+        if self.settings.target != glow::TEXTURE_2D {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "target {:#x}, only 2D textures can be read back",
+                self.settings.target
+            )));
+        }
+
+        let layout = ReadbackLayout::from_internal_format(self.settings.internal_format)
+            .ok_or_else(|| {
+                TextureError::UnsupportedFormat(format!(
+                    "{:#x} for readback",
+                    self.settings.internal_format
+                ))
+            })?;
+
+        let bytes = unsafe {
+            gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            let bytes = self.read_pixels(gl, layout);
+            gl.pixel_store_i32(glow::PACK_ALIGNMENT, 4);
+            bytes?
+        };
+
+        layout
+            .to_image(self.width as u32, self.height as u32, &bytes)
+            .ok_or_else(|| TextureError::InvalidDimensions("readback size mismatch".to_string()))
+    }
+
+    // the format is picked from the extension: png, jpg, tif, exr or hdr.
+    // float textures keep their full precision in exr and hdr, png and tiff get 16 bits
+    pub fn save<P: AsRef<Path>>(&self, gl: &glow::Context, path: P) -> Result<(), TextureError> {
+        save_image(&self.read_to_image(gl)?, path)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe fn read_pixels(
+        &self,
+        gl: &glow::Context,
+        layout: ReadbackLayout,
+    ) -> Result<Vec<u8>, TextureError> {
+        let mut bytes =
+            vec![0u8; self.width as usize * self.height as usize * layout.bytes_per_pixel()];
+        let (format, data_type) = layout.pack_format();
+
+        self.bind(gl);
+        gl.get_tex_image(
+            self.settings.target,
+            0,
+            format,
+            data_type,
+            glow::PixelPackData::Slice(&mut bytes),
+        );
+        self.unbind(gl);
+        Ok(bytes)
+    }
+
+    // WebGL has no glGetTexImage, the texture is attached to a framebuffer and read from there
+    // as RGBA, the only format WebGL2 guarantees, then repacked on the CPU
+    #[cfg(target_arch = "wasm32")]
+    unsafe fn read_pixels(
+        &self,
+        gl: &glow::Context,
+        layout: ReadbackLayout,
+    ) -> Result<Vec<u8>, TextureError> {
+        let (format, data_type) = layout.rgba_pack_format();
+        let bytes_per_pixel = if layout.is_float() { 16 } else { 4 };
+        let mut rgba = vec![0u8; self.width as usize * self.height as usize * bytes_per_pixel];
+
+        let framebuffer = gl
+            .create_framebuffer()
+            .expect("could not create frame buffer");
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+        gl.framebuffer_texture_2d(
+            glow::FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            self.settings.target,
+            self.handle,
+            0,
+        );
+
+        // float textures need EXT_color_buffer_float to be attachable
+        let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+        if status == glow::FRAMEBUFFER_COMPLETE {
+            gl.read_pixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                format,
+                data_type,
+                glow::PixelPackData::Slice(&mut rgba),
+            );
+        }
+        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        gl.delete_framebuffer(framebuffer);
+
+        if status != glow::FRAMEBUFFER_COMPLETE {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "{:#x} for readback, the framebuffer is incomplete ( status {:#x} )",
+                self.settings.internal_format, status
+            )));
+        }
+        Ok(layout.repack_rgba(&rgba))
+    }
+}
+
+impl Fbo {
+    // the color texture flipped so the first row is the top of what was rendered
+    pub fn read_to_image(&self, gl: &glow::Context) -> Result<DynamicImage, TextureError> {
+        let texture = self.texture.as_ref().ok_or_else(|| {
+            TextureError::UnsupportedFormat("fbo without a color texture".to_string())
+        })?;
+        Ok(texture.read_to_image(gl)?.flipv())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, gl: &glow::Context, path: P) -> Result<(), TextureError> {
+        save_image(&self.read_to_image(gl)?, path)
+    }
+}
+
+pub fn save_image<P: AsRef<Path>>(img: &DynamicImage, path: P) -> Result<(), TextureError> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    // the image crate has no hdr in DynamicImage::save
+    if extension == "hdr" {
+        let img = img.to_rgb32f();
+        let file = std::fs::File::create(path).map_err(image::ImageError::IoError)?;
+        let pixels: Vec<image::Rgb<f32>> = img.pixels().copied().collect();
+        image::codecs::hdr::HdrEncoder::new(std::io::BufWriter::new(file)).encode(
+            &pixels,
+            img.width() as usize,
+            img.height() as usize,
+        )?;
+        return Ok(());
+    }
+
+    let converted = convert_for_extension(img, &extension)?;
+    converted.as_ref().unwrap_or(img).save(path)?;
+    Ok(())
+}
+
+// converts to a layout the encoder takes, None if the image can be saved as is
+fn convert_for_extension(
+    img: &DynamicImage,
+    extension: &str,
+) -> Result<Option<DynamicImage>, TextureError> {
+    use image::DynamicImage::*;
+
+    let converted = match (extension, img) {
+        ("exr", ImageRgb32F(_)) | ("exr", ImageRgba32F(_)) => None,
+        ("exr", _) if img.color().has_alpha() => Some(ImageRgba32F(img.to_rgba32f())),
+        ("exr", _) => Some(ImageRgb32F(img.to_rgb32f())),
+
+        ("jpg", ImageLuma8(_)) | ("jpeg", ImageLuma8(_)) => None,
+        ("jpg", _) | ("jpeg", _) => Some(ImageRgb8(img.to_rgb8())),
+
+        ("png", ImageRgb32F(_)) | ("tif", ImageRgb32F(_)) | ("tiff", ImageRgb32F(_)) => {
+            Some(ImageRgb16(img.to_rgb16()))
+        }
+        ("png", ImageRgba32F(_)) | ("tif", ImageRgba32F(_)) | ("tiff", ImageRgba32F(_)) => {
+            Some(ImageRgba16(img.to_rgba16()))
+        }
+        ("png", _) => None,
+
+        // tiff has no gray with alpha
+        ("tif", ImageLumaA8(_)) | ("tiff", ImageLumaA8(_)) => Some(ImageRgba8(img.to_rgba8())),
+        ("tif", ImageLumaA16(_)) | ("tiff", ImageLumaA16(_)) => Some(ImageRgba16(img.to_rgba16())),
+        ("tif", _) | ("tiff", _) => None,
+
+        _ => {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "'{}' for saving, use png, jpg, tif, exr or hdr",
+                extension
+            )))
+        }
+    };
+    Ok(converted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readback_layouts() {
+        assert_eq!(
+            ReadbackLayout::from_internal_format(glow::SRGB8_ALPHA8),
+            Some(ReadbackLayout::Rgba8)
+        );
+        assert_eq!(
+            ReadbackLayout::from_internal_format(glow::RGBA16F),
+            Some(ReadbackLayout::Rgba32F)
+        );
+        assert_eq!(
+            ReadbackLayout::from_internal_format(glow::DEPTH_COMPONENT24),
+            None
+        );
+
+        let bytes: Vec<u8> = [0.5f32, 1.0, 2.0, 4.0]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        let img = ReadbackLayout::Rgba32F.to_image(1, 1, &bytes).unwrap();
+        assert_eq!(
+            img.as_rgba32f().unwrap().get_pixel(0, 0).0,
+            [0.5, 1.0, 2.0, 4.0]
+        );
+        assert!(ReadbackLayout::Rgba16.to_image(2, 2, &bytes).is_none());
+    }
+
+    #[test]
+    fn repacks_rgba_reads() {
+        let rgba = [10u8, 20, 30, 40, 50, 60, 70, 80];
+        assert_eq!(ReadbackLayout::Luma8.repack_rgba(&rgba), vec![10, 50]);
+        assert_eq!(
+            ReadbackLayout::Rgb8.repack_rgba(&rgba),
+            vec![10, 20, 30, 50, 60, 70]
+        );
+        assert_eq!(ReadbackLayout::Rgba8.repack_rgba(&rgba), rgba.to_vec());
+
+        let luma16 = ReadbackLayout::Luma16.repack_rgba(&[255, 0, 0, 255]);
+        assert_eq!(luma16, u16::MAX.to_ne_bytes().to_vec());
+
+        let float: Vec<u8> = [0.5f32, 1.0, 2.0, 4.0]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        assert_eq!(
+            ReadbackLayout::Rgb32F.repack_rgba(&float),
+            float[..12].to_vec()
+        );
+    }
+
+    #[test]
+    fn save_conversions() {
+        let float = DynamicImage::ImageRgba32F(image::Rgba32FImage::new(2, 2));
+        assert!(convert_for_extension(&float, "exr").unwrap().is_none());
+        assert!(matches!(
+            convert_for_extension(&float, "png").unwrap(),
+            Some(DynamicImage::ImageRgba16(_))
+        ));
+
+        let gray_alpha = DynamicImage::ImageLumaA8(image::GrayAlphaImage::new(2, 2));
+        assert!(matches!(
+            convert_for_extension(&gray_alpha, "tiff").unwrap(),
+            Some(DynamicImage::ImageRgba8(_))
+        ));
+        assert!(matches!(
+            convert_for_extension(&gray_alpha, "jpg").unwrap(),
+            Some(DynamicImage::ImageRgb8(_))
+        ));
+        assert!(convert_for_extension(&gray_alpha, "bmp").is_err());
+    }
+}
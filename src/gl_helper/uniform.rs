@@ -0,0 +1,166 @@
+use crate::gl_helper::program_reflection::SAMPLER_TYPES;
+use crate::gl_helper::Texture;
+use glow::{self, HasContext};
+
+/// A value that can be written to a uniform with `GlslProg::set`.
+/// `GL_TYPES` are the GLSL types it can be assigned to, the first one is used in mismatch reports.
+pub trait Uniform {
+    const GL_TYPES: &'static [u32];
+
+    fn set_uniform(&self, gl: &glow::Context, location: &glow::UniformLocation);
+}
+
+/// Types that can also be written as a `[T; N]` uniform array in a single call.
+pub trait UniformArrayElement: Uniform + Sized {
+    fn set_uniform_array(gl: &glow::Context, location: &glow::UniformLocation, values: &[Self]);
+}
+
+impl<T: Uniform> Uniform for &T {
+    const GL_TYPES: &'static [u32] = T::GL_TYPES;
+
+    fn set_uniform(&self, gl: &glow::Context, location: &glow::UniformLocation) {
+        (*self).set_uniform(gl, location);
+    }
+}
+
+impl<T: UniformArrayElement, const N: usize> Uniform for [T; N] {
+    const GL_TYPES: &'static [u32] = T::GL_TYPES;
+
+    fn set_uniform(&self, gl: &glow::Context, location: &glow::UniformLocation) {
+        T::set_uniform_array(gl, location, self);
+    }
+}
+
+macro_rules! impl_uniform {
+    ($ty:ty, [$($gl_type:expr),+], |$gl:ident, $loc:ident, $values:ident| $set_array:expr) => {
+        impl Uniform for $ty {
+            const GL_TYPES: &'static [u32] = &[$($gl_type),+];
+
+            fn set_uniform(&self, gl: &glow::Context, location: &glow::UniformLocation) {
+                Self::set_uniform_array(gl, location, core::slice::from_ref(self));
+            }
+        }
+
+        impl UniformArrayElement for $ty {
+            fn set_uniform_array(
+                $gl: &glow::Context,
+                $loc: &glow::UniformLocation,
+                $values: &[Self],
+            ) {
+                unsafe { $set_array }
+            }
+        }
+    };
+}
+
+// flattens vectors and matrices in to their components
+fn components<T: Copy, const N: usize>(values: &[T], to_array: impl Fn(T) -> [f32; N]) -> Vec<f32> {
+    values.iter().flat_map(|v| to_array(*v)).collect()
+}
+
+fn int_components<T: Copy, const N: usize>(
+    values: &[T],
+    to_array: impl Fn(T) -> [i32; N],
+) -> Vec<i32> {
+    values.iter().flat_map(|v| to_array(*v)).collect()
+}
+
+fn uint_components<T: Copy, const N: usize>(
+    values: &[T],
+    to_array: impl Fn(T) -> [u32; N],
+) -> Vec<u32> {
+    values.iter().flat_map(|v| to_array(*v)).collect()
+}
+
+impl_uniform!(f32, [glow::FLOAT], |gl, loc, values| {
+    gl.uniform_1_f32_slice(Some(loc), values)
+});
+impl_uniform!(i32, [glow::INT, glow::BOOL], |gl, loc, values| {
+    gl.uniform_1_i32_slice(Some(loc), values)
+});
+impl_uniform!(u32, [glow::UNSIGNED_INT, glow::BOOL], |gl, loc, values| {
+    gl.uniform_1_u32_slice(Some(loc), values)
+});
+impl_uniform!(bool, [glow::BOOL], |gl, loc, values| {
+    let values: Vec<i32> = values.iter().map(|v| *v as i32).collect();
+    gl.uniform_1_i32_slice(Some(loc), &values)
+});
+
+impl_uniform!(glam::Vec2, [glow::FLOAT_VEC2], |gl, loc, values| {
+    gl.uniform_2_f32_slice(Some(loc), &components(values, |v| v.to_array()))
+});
+impl_uniform!(glam::Vec3, [glow::FLOAT_VEC3], |gl, loc, values| {
+    gl.uniform_3_f32_slice(Some(loc), &components(values, |v| v.to_array()))
+});
+impl_uniform!(glam::Vec4, [glow::FLOAT_VEC4], |gl, loc, values| {
+    gl.uniform_4_f32_slice(Some(loc), &components(values, |v| v.to_array()))
+});
+impl_uniform!(
+    glam::IVec2,
+    [glow::INT_VEC2, glow::BOOL_VEC2],
+    |gl, loc, values| {
+        gl.uniform_2_i32_slice(Some(loc), &int_components(values, |v| v.to_array()))
+    }
+);
+impl_uniform!(
+    glam::IVec3,
+    [glow::INT_VEC3, glow::BOOL_VEC3],
+    |gl, loc, values| {
+        gl.uniform_3_i32_slice(Some(loc), &int_components(values, |v| v.to_array()))
+    }
+);
+impl_uniform!(
+    glam::IVec4,
+    [glow::INT_VEC4, glow::BOOL_VEC4],
+    |gl, loc, values| {
+        gl.uniform_4_i32_slice(Some(loc), &int_components(values, |v| v.to_array()))
+    }
+);
+impl_uniform!(glam::UVec2, [glow::UNSIGNED_INT_VEC2], |gl, loc, values| {
+    gl.uniform_2_u32_slice(Some(loc), &uint_components(values, |v| v.to_array()))
+});
+impl_uniform!(glam::UVec3, [glow::UNSIGNED_INT_VEC3], |gl, loc, values| {
+    gl.uniform_3_u32_slice(Some(loc), &uint_components(values, |v| v.to_array()))
+});
+impl_uniform!(glam::UVec4, [glow::UNSIGNED_INT_VEC4], |gl, loc, values| {
+    gl.uniform_4_u32_slice(Some(loc), &uint_components(values, |v| v.to_array()))
+});
+
+impl_uniform!(glam::Mat2, [glow::FLOAT_MAT2], |gl, loc, values| {
+    gl.uniform_matrix_2_f32_slice(Some(loc), false, &components(values, |m| m.to_cols_array()))
+});
+impl_uniform!(glam::Mat3, [glow::FLOAT_MAT3], |gl, loc, values| {
+    gl.uniform_matrix_3_f32_slice(Some(loc), false, &components(values, |m| m.to_cols_array()))
+});
+impl_uniform!(glam::Mat4, [glow::FLOAT_MAT4], |gl, loc, values| {
+    gl.uniform_matrix_4_f32_slice(Some(loc), false, &components(values, |m| m.to_cols_array()))
+});
+
+/// Binds `texture` to texture unit `unit` and points the sampler uniform at it.
+///
+/// ```ignore
+/// prog.set(gl, "tex0", glh::TextureBinding::new(&texture, 0));
+/// ```
+#[derive(Clone, Copy)]
+pub struct TextureBinding<'a> {
+    pub texture: &'a Texture,
+    pub unit: u32,
+}
+
+impl<'a> TextureBinding<'a> {
+    pub fn new(texture: &'a Texture, unit: u32) -> Self {
+        Self { texture, unit }
+    }
+}
+
+impl<'a> Uniform for TextureBinding<'a> {
+    const GL_TYPES: &'static [u32] = SAMPLER_TYPES;
+
+    fn set_uniform(&self, gl: &glow::Context, location: &glow::UniformLocation) {
+        unsafe {
+            gl.active_texture(glow::TEXTURE0 + self.unit);
+            gl.bind_texture(self.texture.settings.target, self.texture.handle);
+            gl.uniform_1_i32(Some(location), self.unit as i32);
+        }
+    }
+}
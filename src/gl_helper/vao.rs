@@ -3,9 +3,11 @@ use crate::gl_helper::Vbo;
 use glow;
 use glow::HasContext;
 
-use std::collections::hash_map::HashMap;
-
+use super::vertex_data::{slice_as_bytes, VertexData};
+use super::LayoutAttrib;
 use super::StockShader;
+use super::Vertex;
+use super::VertexLayout;
 
 /*
 TODO: add traits to Vertex Attribs and use a templated version of it
@@ -14,12 +16,58 @@ TODO: add traits to Vertex Attribs and use a templated version of it
     get_size();
 */
 
+/// Type of each component of a vertex attribute, as stored in the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttribType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F16,
+    F32,
+}
+
+impl AttribType {
+    pub fn gl_type(&self) -> u32 {
+        match self {
+            AttribType::U8 => glow::UNSIGNED_BYTE,
+            AttribType::I8 => glow::BYTE,
+            AttribType::U16 => glow::UNSIGNED_SHORT,
+            AttribType::I16 => glow::SHORT,
+            AttribType::U32 => glow::UNSIGNED_INT,
+            AttribType::I32 => glow::INT,
+            AttribType::F16 => glow::HALF_FLOAT,
+            AttribType::F32 => glow::FLOAT,
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        match self {
+            AttribType::U8 | AttribType::I8 => 1,
+            AttribType::U16 | AttribType::I16 | AttribType::F16 => 2,
+            AttribType::U32 | AttribType::I32 | AttribType::F32 => 4,
+        }
+    }
+
+    pub const fn is_float(&self) -> bool {
+        matches!(self, AttribType::F16 | AttribType::F32)
+    }
+}
+
 pub struct VertexAttrib<'a> {
     pub name: &'static str,
     pub size: i32,
     pub stride: i32,
     pub data: &'a [u8],
     pub per_instance: bool, // alias to attrib divisor
+
+    pub component_type: AttribType,
+    pub normalized: bool, // integer data is mapped to [0, 1] or [-1, 1] when read as float
+    pub integer: bool,    // integer data is read as int / uint in the shader ( ivec, uvec )
+    pub location: Option<u32>, // fixed shader location, otherwise resolved by name
+    pub columns: i32,     // matrices use one location per column, with `size` rows each
 }
 
 impl<'a> VertexAttrib<'a> {
@@ -29,12 +77,27 @@ impl<'a> VertexAttrib<'a> {
         stride: i32,
         data: &Vec<T>,
         per_instance: bool,
+    ) -> Self {
+        Self::new_with_type(
+            name,
+            number_of_elements_per_component,
+            stride,
+            data,
+            per_instance,
+            AttribType::F32,
+        )
+    }
+
+    pub fn new_with_type<T>(
+        name: &'static str,
+        number_of_elements_per_component: i32,
+        stride: i32,
+        data: &[T],
+        per_instance: bool,
+        component_type: AttribType,
     ) -> Self {
         let data: &[u8] = unsafe {
-            core::slice::from_raw_parts(
-                data.as_ptr() as *const u8,
-                data.len() * core::mem::size_of::<T>(),
-            )
+            core::slice::from_raw_parts(data.as_ptr() as *const u8, core::mem::size_of_val(data))
         };
 
         VertexAttrib {
@@ -43,9 +106,65 @@ impl<'a> VertexAttrib<'a> {
             stride,
             data,
             per_instance,
+            component_type,
+            normalized: false,
+            integer: false,
+            location: None,
+            columns: 1,
         }
     }
 
+    // integer data will be normalized when read in the shader as float
+    pub fn normalized(mut self) -> Self {
+        self.normalized = true;
+        self.integer = false;
+        self
+    }
+
+    // integer data will be read in the shader as int / uint, using vertex_attrib_pointer_i32
+    pub fn integer(mut self) -> Self {
+        assert!(
+            !self.component_type.is_float(),
+            "integer attribute {} must have an integer component type",
+            self.name
+        );
+        self.integer = true;
+        self.normalized = false;
+        self
+    }
+
+    pub fn location(mut self, location: u32) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    // mat4 per vertex or per instance, takes 4 consecutive locations
+    pub fn new_mat4(name: &'static str, data: &[glam::Mat4], per_instance: bool) -> Self {
+        let mut attrib = Self::new_with_type(name, 4, 0, data, per_instance, AttribType::F32);
+        attrib.columns = 4;
+        attrib
+    }
+
+    // mat3 per vertex or per instance, takes 3 consecutive locations
+    pub fn new_mat3(name: &'static str, data: &[glam::Mat3], per_instance: bool) -> Self {
+        let mut attrib = Self::new_with_type(name, 3, 0, data, per_instance, AttribType::F32);
+        attrib.columns = 3;
+        attrib
+    }
+
+    // size in bytes of one element of this attribute ( ie: one vertex or one instance )
+    pub fn element_size(&self) -> usize {
+        if self.stride > 0 {
+            self.stride as usize
+        } else {
+            (self.size * self.columns) as usize * self.component_type.size_in_bytes()
+        }
+    }
+
+    pub fn number_of_elements(&self) -> usize {
+        self.data.len() / self.element_size()
+    }
+
     pub fn new_position_attr_with_data(data: &Vec<f32>) -> Self {
         let data: &[u8] = unsafe {
             core::slice::from_raw_parts(
@@ -60,6 +179,11 @@ impl<'a> VertexAttrib<'a> {
             stride: 0,
             data,
             per_instance: false,
+            component_type: AttribType::F32,
+            normalized: false,
+            integer: false,
+            location: None,
+            columns: 1,
         };
         position_attr
     }
@@ -78,11 +202,29 @@ impl<'a> VertexAttrib<'a> {
             stride: 0,
             data,
             per_instance: false,
+            component_type: AttribType::F32,
+            normalized: false,
+            integer: false,
+            location: None,
+            columns: 1,
         };
 
         color_attrib
     }
 
+    // packed RGBA8 colors, 4 bytes per vertex normalized to [0, 1]
+    pub fn new_color_attr_with_u8_data(data: &[u8]) -> Self {
+        Self::new_with_type(
+            StockShader::attrib_name_color(),
+            4,
+            0,
+            data,
+            false,
+            AttribType::U8,
+        )
+        .normalized()
+    }
+
     pub fn new_texture_attr_with_data(data: &Vec<f32>) -> Self {
         let data: &[u8] = unsafe {
             core::slice::from_raw_parts(
@@ -97,11 +239,54 @@ impl<'a> VertexAttrib<'a> {
             stride: 0,
             data,
             per_instance: false,
+            component_type: AttribType::F32,
+            normalized: false,
+            integer: false,
+            location: None,
+            columns: 1,
         };
 
         texture_attrib
     }
 
+    // per instance attributes of the instanced stock shaders
+    pub fn new_instance_translation_attr_with_data(data: &[glam::Vec3]) -> Self {
+        Self::new_with_type(
+            StockShader::attrib_name_instance_translation(),
+            3,
+            0,
+            data,
+            true,
+            AttribType::F32,
+        )
+    }
+
+    pub fn new_instance_transform_attr_with_data(data: &[glam::Mat4]) -> Self {
+        Self::new_mat4(StockShader::attrib_name_instance_transform(), data, true)
+    }
+
+    pub fn new_instance_color_attr_with_data(data: &[glam::Vec4]) -> Self {
+        Self::new_with_type(
+            StockShader::attrib_name_instance_color(),
+            4,
+            0,
+            data,
+            true,
+            AttribType::F32,
+        )
+    }
+
+    pub fn new_instance_uv_offset_attr_with_data(data: &[glam::Vec2]) -> Self {
+        Self::new_with_type(
+            StockShader::attrib_name_instance_uv_offset(),
+            2,
+            0,
+            data,
+            true,
+            AttribType::F32,
+        )
+    }
+
     pub fn new_normal_attr_with_data(data: &Vec<f32>) -> Self {
         let data: &[u8] = unsafe {
             core::slice::from_raw_parts(
@@ -116,16 +301,53 @@ impl<'a> VertexAttrib<'a> {
             stride: 0,
             data,
             per_instance: false,
+            component_type: AttribType::F32,
+            normalized: false,
+            integer: false,
+            location: None,
+            columns: 1,
         };
 
         texture_attrib
     }
 }
 
+/// A sub range of a vao, `first` and `count` are in indices for indexed vaos and in vertices otherwise.
+/// `base_vertex` is added to each index ( not supported on webgl2 ).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawRange {
+    pub first: i32,
+    pub count: i32,
+    pub base_vertex: i32,
+}
+
+impl DrawRange {
+    pub fn new(first: i32, count: i32) -> Self {
+        Self {
+            first,
+            count,
+            base_vertex: 0,
+        }
+    }
+
+    pub fn base_vertex(mut self, base_vertex: i32) -> Self {
+        self.base_vertex = base_vertex;
+        self
+    }
+}
+
+// a vbo and the layout of the attributes stored in it
+struct VaoBuffer {
+    layout: VertexLayout,
+    locations: Vec<Option<u32>>, // shader location of each attribute in the layout
+    vbo: Vbo,
+}
+
 pub struct Vao {
     handle: Option<glow::VertexArray>,
-    vbos: HashMap<&'static str, Vbo>,
+    buffers: Vec<VaoBuffer>,
     num_of_vertices: usize,
+    num_of_instances: usize,
     index_buffer: Option<Vbo>,
     draw_mode: u32,
 }
@@ -139,15 +361,7 @@ impl Vao {
         shader: &GlslProg,
     ) -> Option<Vao> {
         let mut vao = Self::new_from_attrib(gl, attribs, mode, shader).unwrap();
-        let index_vbo = Vbo::new(gl, indices, glow::ELEMENT_ARRAY_BUFFER);
-
-        vao.bind(gl);
-        index_vbo.bind(gl);
-
-        vao.unbind(gl);
-        index_vbo.unbind(gl);
-
-        vao.index_buffer = Some(index_vbo);
+        vao.set_indices(gl, indices);
         Some(vao)
     }
 
@@ -157,68 +371,291 @@ impl Vao {
         mode: u32,
         shader: &GlslProg,
     ) -> Option<Self> {
-        let num_of_vertices =
-            attribs[0].data.len() / (attribs[0].size as usize * core::mem::size_of::<f32>());
+        let layouts: Vec<VertexLayout> = attribs.iter().map(VertexLayout::from_attrib).collect();
+        let buffers: Vec<(&VertexLayout, &[u8])> = layouts
+            .iter()
+            .zip(attribs.iter())
+            .map(|(layout, attrib)| (layout, attrib.data))
+            .collect();
+
+        Self::new_from_layouts(gl, &buffers, mode, shader)
+    }
+
+    pub fn new_from_layouts_indexed(
+        gl: &glow::Context,
+        buffers: &[(&VertexLayout, &[u8])],
+        indices: &[u32],
+        mode: u32,
+        shader: &GlslProg,
+    ) -> Option<Vao> {
+        let mut vao = Self::new_from_layouts(gl, buffers, mode, shader).unwrap();
+        vao.set_indices(gl, indices);
+        Some(vao)
+    }
+
+    pub fn new_from_vertices<V: Vertex>(
+        gl: &glow::Context,
+        vertices: &[V],
+        mode: u32,
+        shader: &GlslProg,
+    ) -> Option<Self> {
+        let layout = V::layout();
+        Self::new_from_layouts(gl, &[(&layout, slice_as_bytes(vertices))], mode, shader)
+    }
+
+    pub fn new_from_vertices_indexed<V: Vertex>(
+        gl: &glow::Context,
+        vertices: &[V],
+        indices: &[u32],
+        mode: u32,
+        shader: &GlslProg,
+    ) -> Option<Self> {
+        let layout = V::layout();
+        Self::new_from_layouts_indexed(
+            gl,
+            &[(&layout, slice_as_bytes(vertices))],
+            indices,
+            mode,
+            shader,
+        )
+    }
+
+    // each buffer is uploaded to its own vbo, with all the attributes in its layout pointing into it
+    pub fn new_from_layouts(
+        gl: &glow::Context,
+        buffers: &[(&VertexLayout, &[u8])],
+        mode: u32,
+        shader: &GlslProg,
+    ) -> Option<Self> {
+        // vertex count comes from the first per vertex buffer
+        let num_of_vertices = buffers
+            .iter()
+            .find(|(layout, _)| !layout.is_per_instance())
+            .or_else(|| buffers.first())
+            .map(|(layout, data)| layout.number_of_elements(data.len()))
+            .unwrap_or(0);
+
         let vao_handle = unsafe { gl.create_vertex_array().unwrap() };
 
-        let mut attrib_map: HashMap<&'static str, Vbo> = HashMap::new();
+        let mut vao = Self {
+            draw_mode: mode,
+            handle: Some(vao_handle),
+            buffers: Vec::new(),
+            num_of_vertices,
+            num_of_instances: 0,
+            index_buffer: None,
+        };
+
+        for (layout, data) in buffers {
+            vao.add_layout(gl, layout, data, shader);
+        }
+
+        Some(vao)
+    }
+
+    // adds a buffer of per instance data, with one V per instance
+    pub fn add_instances<V: Vertex>(
+        &mut self,
+        gl: &glow::Context,
+        instances: &[V],
+        shader: &GlslProg,
+    ) {
+        let layout = V::layout().per_instance();
+        self.add_layout(gl, &layout, slice_as_bytes(instances), shader);
+    }
+
+    pub fn add_attrib(&mut self, gl: &glow::Context, attrib: &VertexAttrib, shader: &GlslProg) {
+        self.add_layout(gl, &VertexLayout::from_attrib(attrib), attrib.data, shader);
+    }
+
+    // adds a new buffer to the vao, the first per instance buffer sets the instance count
+    pub fn add_layout(
+        &mut self,
+        gl: &glow::Context,
+        layout: &VertexLayout,
+        data: &[u8],
+        shader: &GlslProg,
+    ) {
+        let data_vbo = Vbo::new_from_raw_parts(
+            gl,
+            data,
+            layout.number_of_elements(data.len()),
+            glow::ARRAY_BUFFER,
+        );
+
+        let mut locations = Vec::new();
 
         unsafe {
-            gl.bind_vertex_array(Some(vao_handle));
+            gl.bind_vertex_array(self.handle);
+            gl.bind_buffer(data_vbo.get_gl_type(), data_vbo.get_handle());
+
+            for attrib in &layout.attribs {
+                let loc = match Self::resolve_location(gl, attrib, shader) {
+                    Some(loc) => loc,
+                    None => {
+                        locations.push(None);
+                        continue;
+                    }
+                };
+
+                // one location per column, matrices are stored column major
+                let column_size = attrib.size * attrib.component_type.size_in_bytes() as i32;
+                for column in 0..attrib.columns.max(1) {
+                    let column_loc = loc + column as u32;
+                    let offset = attrib.offset + column * column_size;
+
+                    gl.enable_vertex_attrib_array(column_loc);
+
+                    if attrib.integer {
+                        gl.vertex_attrib_pointer_i32(
+                            column_loc,
+                            attrib.size,
+                            attrib.component_type.gl_type(),
+                            layout.stride,
+                            offset,
+                        );
+                    } else {
+                        gl.vertex_attrib_pointer_f32(
+                            column_loc,
+                            attrib.size,
+                            attrib.component_type.gl_type(),
+                            attrib.normalized,
+                            layout.stride,
+                            offset,
+                        );
+                    }
+
+                    gl.vertex_attrib_divisor(column_loc, layout.divisor);
+                }
+                locations.push(Some(loc));
+            }
+
+            // end
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(data_vbo.get_gl_type(), None);
+        }
+
+        if layout.is_per_instance() && self.num_of_instances == 0 {
+            self.num_of_instances = data_vbo.len() * layout.divisor as usize;
+        }
+
+        self.buffers.push(VaoBuffer {
+            layout: layout.clone(),
+            locations,
+            vbo: data_vbo,
+        });
+    }
+
+    // explicit location first, then the fixed stock locations, and last whatever the shader linked
+    fn resolve_location(
+        gl: &glow::Context,
+        attrib: &LayoutAttrib,
+        shader: &GlslProg,
+    ) -> Option<u32> {
+        // the linked program wins over the stock table, a custom shader may reuse a stock
+        // name at another location. The table covers attributes the compiler stripped out
+        attrib
+            .location
+            .or_else(|| shader.get_attrib_location(gl, attrib.name))
+            .or_else(|| StockShader::attrib_location(attrib.name))
+    }
+
+    // disables the attribute, the buffer is deleted once none of its attributes are in use
+    pub fn remove_attrib(&mut self, gl: &glow::Context, name: &str) {
+        let buffer_index = match self.find_buffer(name) {
+            Some(index) => index,
+            None => {
+                println!("no attribute named: {}", name);
+                return;
+            }
         };
 
-        for i in 0..attribs.len() {
-            let attrib = &attribs[i];
-            let name = attrib.name;
+        let buffer = &mut self.buffers[buffer_index];
+        let attrib_index = buffer
+            .layout
+            .attribs
+            .iter()
+            .position(|a| a.name == name)
+            .unwrap();
+
+        let loc = buffer.locations.remove(attrib_index);
+        let attrib = buffer.layout.attribs.remove(attrib_index);
 
+        if let Some(loc) = loc {
             unsafe {
-                let loc = gl
-                    .get_attrib_location(
-                        shader
-                            .get_handle()
-                            .expect("provided GlslProg is NONE, did it compiled properly? "),
-                        name,
-                    )
-                    .expect(format!("unable to find attribute with name: {}", name).as_str());
-
-                gl.enable_vertex_attrib_array(loc);
-
-                let data_vbo =
-                    Vbo::new_from_raw_parts(gl, attrib.data, num_of_vertices, glow::ARRAY_BUFFER);
-                gl.bind_buffer(data_vbo.get_gl_type(), data_vbo.get_handle());
-
-                gl.vertex_attrib_pointer_f32(
-                    loc,
-                    attrib.size,
-                    glow::FLOAT,
-                    false,
-                    attrib.stride,
-                    0,
-                );
-
-                let attrib_divisor: u32 = if attrib.per_instance { 1 } else { 0 };
-
-                gl.vertex_attrib_divisor(loc, attrib_divisor);
-
-                // end
-                gl.bind_buffer(data_vbo.get_gl_type(), None);
-                attrib_map.insert(name, data_vbo);
-            };
+                gl.bind_vertex_array(self.handle);
+                for column in 0..attrib.columns.max(1) {
+                    gl.disable_vertex_attrib_array(loc + column as u32);
+                }
+                gl.bind_vertex_array(None);
+            }
         }
 
-        unsafe {
-            gl.bind_vertex_array(None);
+        if buffer.layout.attribs.is_empty() {
+            let mut buffer = self.buffers.remove(buffer_index);
+            buffer.vbo.delete(gl);
         }
+    }
 
-        // return
-        let vao = Self {
-            draw_mode: mode,
-            handle: Some(vao_handle),
-            vbos: attrib_map,
-            num_of_vertices,
-            index_buffer: None,
+    // replaces the data of the buffer holding the attribute.
+    // for interleaved buffers this is the data of all the attributes in the layout.
+    pub fn update_attrib<T: VertexData>(&mut self, gl: &glow::Context, name: &str, data: &[T]) {
+        let data = slice_as_bytes(data);
+
+        let buffer = match self.find_buffer(name) {
+            Some(index) => &mut self.buffers[index],
+            None => {
+                println!("no attribute named: {}", name);
+                return;
+            }
         };
-        Some(vao)
+
+        let number_of_elements = buffer.layout.number_of_elements(data.len());
+        buffer.vbo.update(gl, data, number_of_elements);
+
+        if buffer.layout.is_per_instance() {
+            self.num_of_instances = number_of_elements * buffer.layout.divisor as usize;
+        } else {
+            self.num_of_vertices = number_of_elements;
+        }
+    }
+
+    pub fn attrib_buffer(&self, name: &str) -> Option<&Vbo> {
+        self.find_buffer(name).map(|index| &self.buffers[index].vbo)
+    }
+
+    fn find_buffer(&self, name: &str) -> Option<usize> {
+        self.buffers
+            .iter()
+            .position(|buffer| buffer.layout.contains(name))
+    }
+
+    pub fn set_num_of_vertices(&mut self, num_of_vertices: usize) {
+        self.num_of_vertices = num_of_vertices;
+    }
+
+    pub fn get_num_of_vertices(&self) -> usize {
+        self.num_of_vertices
+    }
+
+    pub fn set_num_of_instances(&mut self, num_of_instances: usize) {
+        self.num_of_instances = num_of_instances;
+    }
+
+    pub fn get_num_of_instances(&self) -> usize {
+        self.num_of_instances
+    }
+
+    fn set_indices(&mut self, gl: &glow::Context, indices: &[u32]) {
+        let index_vbo = Vbo::new(gl, indices, glow::ELEMENT_ARRAY_BUFFER);
+
+        self.bind(gl);
+        index_vbo.bind(gl);
+
+        self.unbind(gl);
+        index_vbo.unbind(gl);
+
+        self.index_buffer = Some(index_vbo);
     }
 
     pub fn set_draw_mode(&mut self, mode: u32) {
@@ -229,6 +666,10 @@ impl Vao {
         self.draw_mode
     }
 
+    pub fn get_layouts(&self) -> Vec<&VertexLayout> {
+        self.buffers.iter().map(|buffer| &buffer.layout).collect()
+    }
+
     pub fn get_handle(&self) -> Option<glow::VertexArray> {
         self.handle
     }
@@ -248,40 +689,123 @@ impl Vao {
     }
 
     pub fn draw_instanced(&self, gl: &glow::Context, instance_count: i32) {
-        unsafe {
-            self.bind(gl);
-            //gl::DrawArraysInstanced(primitive, 0, self.num_of_vertices as i32, instance_count);
-            gl.draw_arrays_instanced(
-                self.draw_mode,
-                0,
-                self.num_of_vertices as i32,
-                instance_count,
-            );
-            self.unbind(gl);
-        }
+        self.bind(gl);
+        self.draw_range_unbound(gl, &self.full_range(), Some(instance_count));
+        self.unbind(gl);
     }
 
     pub fn draw(&self, gl: &glow::Context) {
         self.bind(gl);
-        match &self.index_buffer {
-            Some(element_buffer) => unsafe {
-                gl.draw_elements(
-                    self.draw_mode,
-                    element_buffer.len() as i32,
-                    glow::UNSIGNED_INT,
-                    0,
-                );
-            },
-            None => unsafe {
-                gl.draw_arrays(self.draw_mode, 0, self.num_of_vertices as i32);
-            },
+        self.draw_range_unbound(gl, &self.full_range(), None);
+        self.unbind(gl);
+    }
+
+    // draws a sub range of the vertices, or of the indices if the vao is indexed
+    pub fn draw_range(&self, gl: &glow::Context, range: &DrawRange) {
+        self.bind(gl);
+        self.draw_range_unbound(gl, range, None);
+        self.unbind(gl);
+    }
+
+    pub fn draw_range_instanced(&self, gl: &glow::Context, range: &DrawRange, instance_count: i32) {
+        self.bind(gl);
+        self.draw_range_unbound(gl, range, Some(instance_count));
+        self.unbind(gl);
+    }
+
+    // draws many sub meshes stored in this vao, binding it only once.
+    // glow has no glMultiDraw*, so this issues one draw call per range.
+    pub fn multi_draw(&self, gl: &glow::Context, ranges: &[DrawRange]) {
+        self.bind(gl);
+        for range in ranges {
+            self.draw_range_unbound(gl, range, None);
         }
         self.unbind(gl);
     }
 
+    pub fn multi_draw_instanced(
+        &self,
+        gl: &glow::Context,
+        ranges: &[DrawRange],
+        instance_count: i32,
+    ) {
+        self.bind(gl);
+        for range in ranges {
+            self.draw_range_unbound(gl, range, Some(instance_count));
+        }
+        self.unbind(gl);
+    }
+
+    fn full_range(&self) -> DrawRange {
+        match &self.index_buffer {
+            Some(element_buffer) => DrawRange::new(0, element_buffer.len() as i32),
+            None => DrawRange::new(0, self.num_of_vertices as i32),
+        }
+    }
+
+    fn draw_range_unbound(
+        &self,
+        gl: &glow::Context,
+        range: &DrawRange,
+        instance_count: Option<i32>,
+    ) {
+        let mode = self.draw_mode;
+
+        unsafe {
+            match (&self.index_buffer, instance_count) {
+                (Some(_), None) => {
+                    let offset = range.first * core::mem::size_of::<u32>() as i32;
+                    if range.base_vertex == 0 {
+                        gl.draw_elements(mode, range.count, glow::UNSIGNED_INT, offset);
+                    } else {
+                        gl.draw_elements_base_vertex(
+                            mode,
+                            range.count,
+                            glow::UNSIGNED_INT,
+                            offset,
+                            range.base_vertex,
+                        );
+                    }
+                }
+                (Some(_), Some(instance_count)) => {
+                    let offset = range.first * core::mem::size_of::<u32>() as i32;
+                    if range.base_vertex == 0 {
+                        gl.draw_elements_instanced(
+                            mode,
+                            range.count,
+                            glow::UNSIGNED_INT,
+                            offset,
+                            instance_count,
+                        );
+                    } else {
+                        gl.draw_elements_instanced_base_vertex(
+                            mode,
+                            range.count,
+                            glow::UNSIGNED_INT,
+                            offset,
+                            instance_count,
+                            range.base_vertex,
+                        );
+                    }
+                }
+                (None, None) => {
+                    gl.draw_arrays(mode, range.first + range.base_vertex, range.count);
+                }
+                (None, Some(instance_count)) => {
+                    gl.draw_arrays_instanced(
+                        mode,
+                        range.first + range.base_vertex,
+                        range.count,
+                        instance_count,
+                    );
+                }
+            }
+        }
+    }
+
     pub fn delete(&mut self, gl: &glow::Context) {
-        for (_, val) in &mut self.vbos {
-            val.delete(gl);
+        for buffer in &mut self.buffers {
+            buffer.vbo.delete(gl);
         }
 
         unsafe {
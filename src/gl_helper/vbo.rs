@@ -42,6 +42,23 @@ impl Vbo {
         }
     }
 
+    // replaces the buffer contents, reallocating the storage only when the size changes
+    pub fn update(&mut self, gl: &glow::Context, data: &[u8], number_of_items: usize) {
+        unsafe {
+            gl.bind_buffer(self.gl_type, self.handle);
+
+            let current_size = gl.get_buffer_parameter_i32(self.gl_type, glow::BUFFER_SIZE);
+            if current_size as usize == data.len() {
+                gl.buffer_sub_data_u8_slice(self.gl_type, 0, data);
+            } else {
+                gl.buffer_data_u8_slice(self.gl_type, data, glow::DYNAMIC_DRAW);
+            }
+
+            gl.bind_buffer(self.gl_type, None);
+        }
+        self.number_of_items = number_of_items;
+    }
+
     pub fn get_handle(&self) -> Option<glow::Buffer> {
         self.handle
     }
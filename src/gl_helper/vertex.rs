@@ -0,0 +1,68 @@
+use crate::gl_helper::{AttribType, VertexData, VertexLayout};
+
+/// Implemented by `#[derive(Vertex)]` for `#[repr(C)]` structs, describes the struct as a `VertexLayout`
+/// so a `Vao` can be built straight from a slice of them.
+pub trait Vertex: VertexData + Sized {
+    fn layout() -> VertexLayout;
+}
+
+/// Component type and count of a type used as a field of a `Vertex` struct.
+pub trait VertexComponent {
+    const COMPONENT_TYPE: AttribType;
+    const SIZE: i32;
+    const COLUMNS: i32 = 1;
+}
+
+macro_rules! impl_vertex_component {
+    ($ty:ty, $component_type:expr, $size:expr) => {
+        impl VertexComponent for $ty {
+            const COMPONENT_TYPE: AttribType = $component_type;
+            const SIZE: i32 = $size;
+        }
+    };
+}
+
+macro_rules! impl_vertex_component_arrays {
+    ($ty:ty, $component_type:expr) => {
+        impl_vertex_component!($ty, $component_type, 1);
+        impl_vertex_component!([$ty; 1], $component_type, 1);
+        impl_vertex_component!([$ty; 2], $component_type, 2);
+        impl_vertex_component!([$ty; 3], $component_type, 3);
+        impl_vertex_component!([$ty; 4], $component_type, 4);
+    };
+}
+
+impl_vertex_component_arrays!(u8, AttribType::U8);
+impl_vertex_component_arrays!(i8, AttribType::I8);
+impl_vertex_component_arrays!(u16, AttribType::U16);
+impl_vertex_component_arrays!(i16, AttribType::I16);
+impl_vertex_component_arrays!(u32, AttribType::U32);
+impl_vertex_component_arrays!(i32, AttribType::I32);
+impl_vertex_component_arrays!(f32, AttribType::F32);
+
+impl_vertex_component!(glam::Vec2, AttribType::F32, 2);
+impl_vertex_component!(glam::Vec3, AttribType::F32, 3);
+impl_vertex_component!(glam::Vec4, AttribType::F32, 4);
+impl_vertex_component!(glam::IVec2, AttribType::I32, 2);
+impl_vertex_component!(glam::IVec3, AttribType::I32, 3);
+impl_vertex_component!(glam::IVec4, AttribType::I32, 4);
+impl_vertex_component!(glam::UVec2, AttribType::U32, 2);
+impl_vertex_component!(glam::UVec3, AttribType::U32, 3);
+impl_vertex_component!(glam::UVec4, AttribType::U32, 4);
+
+macro_rules! impl_vertex_component_matrix {
+    ($ty:ty, $size:expr) => {
+        impl VertexComponent for $ty {
+            const COMPONENT_TYPE: AttribType = AttribType::F32;
+            const SIZE: i32 = $size;
+            const COLUMNS: i32 = $size;
+        }
+    };
+}
+
+impl_vertex_component_matrix!(glam::Mat2, 2);
+impl_vertex_component_matrix!(glam::Mat3, 3);
+impl_vertex_component_matrix!(glam::Mat4, 4);
+impl_vertex_component_matrix!([[f32; 2]; 2], 2);
+impl_vertex_component_matrix!([[f32; 3]; 3], 3);
+impl_vertex_component_matrix!([[f32; 4]; 4], 4);
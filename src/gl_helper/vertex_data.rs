@@ -0,0 +1,29 @@
+/// Types that can be uploaded to a buffer as their raw bytes.
+/// Implemented for the scalar, array and glam types attributes are made of, and by
+/// `#[derive(Vertex)]` for structs without padding.
+///
+/// # Safety
+///
+/// Every byte of the type has to be initialized, so it can't have padding, and it can't hold
+/// pointers or references.
+pub unsafe trait VertexData {}
+
+macro_rules! impl_vertex_data {
+    ($($ty:ty),*) => {
+        $(unsafe impl VertexData for $ty {})*
+    };
+}
+
+impl_vertex_data!(u8, i8, u16, i16, u32, i32, f32);
+impl_vertex_data!(glam::Vec2, glam::Vec3, glam::Vec4);
+impl_vertex_data!(glam::IVec2, glam::IVec3, glam::IVec4);
+impl_vertex_data!(glam::UVec2, glam::UVec3, glam::UVec4);
+impl_vertex_data!(glam::Mat2, glam::Mat3, glam::Mat4);
+
+// the elements of an array are packed, so it has no padding of its own
+unsafe impl<T: VertexData, const N: usize> VertexData for [T; N] {}
+
+// reinterprets a slice of plain data as bytes for uploading
+pub(crate) fn slice_as_bytes<T: VertexData>(data: &[T]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(data.as_ptr() as *const u8, core::mem::size_of_val(data)) }
+}
@@ -0,0 +1,148 @@
+use crate::gl_helper::vao::{AttribType, VertexAttrib};
+
+/// One attribute inside a `VertexLayout`, `offset` is in bytes from the start of the element.
+#[derive(Debug, Clone)]
+pub struct LayoutAttrib {
+    pub name: &'static str,
+    pub size: i32,
+    pub component_type: AttribType,
+    pub normalized: bool,
+    pub integer: bool,
+    pub offset: i32,
+    pub location: Option<u32>,
+    pub columns: i32, // > 1 for matrices, each column takes its own location
+}
+
+impl LayoutAttrib {
+    pub fn new(name: &'static str, size: i32, component_type: AttribType, offset: i32) -> Self {
+        Self {
+            name,
+            size,
+            component_type,
+            normalized: false,
+            integer: false,
+            offset,
+            location: None,
+            columns: 1,
+        }
+    }
+
+    pub fn normalized(mut self) -> Self {
+        self.normalized = true;
+        self.integer = false;
+        self
+    }
+
+    pub fn integer(mut self) -> Self {
+        assert!(
+            !self.component_type.is_float(),
+            "integer attribute {} must have an integer component type",
+            self.name
+        );
+        self.integer = true;
+        self.normalized = false;
+        self
+    }
+
+    pub fn location(mut self, location: u32) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    pub fn columns(mut self, columns: i32) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    pub fn size_in_bytes(&self) -> i32 {
+        self.size * self.columns * self.component_type.size_in_bytes() as i32
+    }
+}
+
+/// Describes how several attributes are packed ( interleaved ) into a single buffer.
+///
+/// ```ignore
+/// // x, y, z, u, v
+/// let layout = VertexLayout::new()
+///     .push("inPosition", 3, AttribType::F32)
+///     .push("inTexture", 2, AttribType::F32);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VertexLayout {
+    pub attribs: Vec<LayoutAttrib>,
+    pub stride: i32,
+    pub divisor: u32,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// appends an attribute right after the previous one, growing the stride
+    pub fn push(self, name: &'static str, size: i32, component_type: AttribType) -> Self {
+        let offset = self.stride;
+        self.push_attrib(LayoutAttrib::new(name, size, component_type, offset))
+    }
+
+    /// adds an attribute with an explicit offset, the stride grows to fit it if needed
+    pub fn push_attrib(mut self, attrib: LayoutAttrib) -> Self {
+        self.stride = self.stride.max(attrib.offset + attrib.size_in_bytes());
+        self.attribs.push(attrib);
+        self
+    }
+
+    /// overrides the computed stride, useful for padded structs
+    pub fn stride(mut self, stride: i32) -> Self {
+        self.stride = stride;
+        self
+    }
+
+    pub fn divisor(mut self, divisor: u32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+
+    pub fn per_instance(self) -> Self {
+        self.divisor(1)
+    }
+
+    pub fn is_per_instance(&self) -> bool {
+        self.divisor != 0
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.attribs.iter().any(|a| a.name == name)
+    }
+
+    /// number of elements ( vertices or instances ) stored in a buffer of `byte_len` bytes
+    pub fn number_of_elements(&self, byte_len: usize) -> usize {
+        if self.stride <= 0 {
+            return 0;
+        }
+        byte_len / self.stride as usize
+    }
+
+    /// single attribute layout, used to keep non interleaved `VertexAttrib`s working
+    pub fn from_attrib(attrib: &VertexAttrib) -> Self {
+        let mut layout_attrib =
+            LayoutAttrib::new(attrib.name, attrib.size, attrib.component_type, 0);
+        layout_attrib.normalized = attrib.normalized;
+        layout_attrib.integer = attrib.integer;
+        layout_attrib.location = attrib.location;
+        layout_attrib.columns = attrib.columns;
+
+        let layout = Self::new().push_attrib(layout_attrib);
+        let layout = if attrib.stride > 0 {
+            layout.stride(attrib.stride)
+        } else {
+            layout
+        };
+
+        if attrib.per_instance {
+            layout.per_instance()
+        } else {
+            layout
+        }
+    }
+}
@@ -1,3 +1,6 @@
+// lets the derive macros refer to ::piralib from inside the crate too
+extern crate self as piralib;
+
 pub mod app;
 pub use self::app::App;
 
@@ -50,6 +50,11 @@ impl Rect {
     }
 
     pub fn texture_coords<'a>(&'a mut self) -> &'a mut Self {
+        self.texture_coords_from(glh::UvRect::FULL)
+    }
+
+    // maps the rect on to a sub rectangle of the texture, ie: a region of a TextureAtlas
+    pub fn texture_coords_from(&mut self, uv: glh::UvRect) -> &mut Self {
         let data = GeometryData::gen_func(
             self,
             self.data.number_of_vertices,
@@ -58,14 +63,14 @@ impl Rect {
                 let vertices = &rect
                     .data
                     .attribs
-                    .get(&glh::StockShader::attrib_name_position().to_string())
+                    .get(glh::StockShader::attrib_name_position())
                     .unwrap();
                 let color_index = index * 2;
                 let position_index = index * 3;
-                color_vertices[color_index] =
-                    (vertices[position_index] - rect.x) / rect.width as f32;
-                color_vertices[color_index + 1] =
-                    (vertices[position_index + 1] - rect.y) / rect.height as f32;
+                color_vertices[color_index] = uv.x
+                    + (vertices[position_index] - rect.x) / rect.width * uv.width;
+                color_vertices[color_index + 1] = uv.y
+                    + (vertices[position_index + 1] - rect.y) / rect.height * uv.height;
             },
         );
 
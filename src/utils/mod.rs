@@ -4,3 +4,4 @@
 pub mod transform_system;
 pub mod camera;
 pub mod geo;
+pub mod skybox;
@@ -0,0 +1,103 @@
+use crate::gl_helper::{self as glh, shader_preprocessor};
+use crate::utils::camera::Camera;
+use crate::utils::geo::{Cuboid, Geometry};
+use glow::HasContext;
+
+/// Draws a cube map behind everything else. Draw it last so the depth test skips the
+/// pixels already covered, the sky is drawn at the far plane.
+pub struct Skybox {
+    vao: glh::Vao,
+    prog: glh::GlslProg,
+    texture: glh::Texture,
+    pub intensity: f32, // scales the sky color, for hdr cube maps
+}
+
+impl Skybox {
+    pub fn new(gl: &glow::Context, texture: glh::Texture) -> Self {
+        assert!(texture.is_cube_map(), "a skybox needs a cube map texture");
+
+        let prog = glh::GlslProg::new(gl, &vertex_shader(), &frag_shader());
+        let vao = Cuboid::new_with_uniform_size(1.0).get_vao(gl, &prog);
+
+        Self {
+            vao,
+            prog,
+            texture,
+            intensity: 1.0,
+        }
+    }
+
+    pub fn get_texture(&self) -> &glh::Texture {
+        &self.texture
+    }
+
+    pub fn set_texture(&mut self, texture: glh::Texture) {
+        assert!(texture.is_cube_map(), "a skybox needs a cube map texture");
+        self.texture = texture;
+    }
+
+    pub fn draw(&self, gl: &glow::Context, view: &glam::Mat4, projection: &glam::Mat4) {
+        // the translation is dropped so the sky stays around the camera
+        let view = glam::Mat4::from_mat3(glam::Mat3::from_mat4(*view));
+
+        unsafe {
+            gl.depth_func(glow::LEQUAL);
+        }
+
+        self.prog.bind(gl);
+        self.prog.set_view_matrix(gl, &view);
+        self.prog.set_perspective_matrix(gl, projection);
+        self.prog.set(gl, "uIntensity", self.intensity);
+        self.prog.bind_texture(gl, "uSkybox", &self.texture);
+        self.vao.draw(gl);
+        self.prog.unbind(gl);
+
+        unsafe {
+            gl.depth_func(glow::LESS);
+        }
+    }
+
+    pub fn draw_with_camera(&self, gl: &glow::Context, camera: &impl Camera) {
+        self.draw(
+            gl,
+            &camera.get_view_matrix(),
+            &camera.get_perspective_matrix(),
+        );
+    }
+}
+
+fn vertex_shader() -> String {
+    format!(
+        "{}
+    uniform mat4 {view};
+    uniform mat4 {perspective};
+    in vec3 {position};
+    out vec3 vDirection;
+    void main()
+    {{
+        vDirection = {position};
+        vec4 position = {perspective} * {view} * vec4({position}, 1.0);
+        // depth of 1.0 after the perspective divide
+        gl_Position = position.xyww;
+    }}",
+        shader_preprocessor::default_header(),
+        view = glh::StockShader::uniform_name_view_matrix(),
+        perspective = glh::StockShader::uniform_name_perspective_matrix(),
+        position = glh::StockShader::attrib_name_position(),
+    )
+}
+
+fn frag_shader() -> String {
+    format!(
+        "{}
+    uniform samplerCube uSkybox;
+    uniform float uIntensity;
+    in vec3 vDirection;
+    out vec4 Color;
+    void main()
+    {{
+        Color = vec4(texture(uSkybox, vDirection).rgb * uIntensity, 1.0);
+    }}",
+        shader_preprocessor::default_header()
+    )
+}